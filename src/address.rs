@@ -0,0 +1,179 @@
+//! This module contains [PciAddress], the location of a PCI device.
+
+use crate::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// The address of a PCI device, made of its domain, bus, device and function numbers.
+///
+/// Addresses are ordered the same way `lspci` orders devices, by domain first and by function last.
+///
+/// # Examples
+///
+/// ```
+/// use aparato::PciAddress;
+///
+/// let address: PciAddress = "10000:e1:00.0".parse().unwrap();
+/// assert_eq!(address.domain, 0x10000);
+/// assert_eq!(address.bus, 0xe1);
+///
+/// // The long form is used by default, the alternate flag omits the domain.
+/// assert_eq!(format!("{}", address), "10000:e1:00.0");
+/// assert_eq!(format!("{:#}", address), "e1:00.0");
+///
+/// // Short addresses belong to domain 0, sysfs paths are accepted as well.
+/// let short: PciAddress = "00:1f.3".parse().unwrap();
+/// let path: PciAddress = "/sys/bus/pci/devices/0000:00:1f.3".parse().unwrap();
+/// assert_eq!(short, path);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PciAddress {
+    /// The PCI domain, also known as the segment group.
+    ///
+    /// Domains past `ffff` are used by Intel VMD.
+    pub domain: u32,
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8,
+}
+
+impl PciAddress {
+    /// This function returns a new `PciAddress`, or `None` if `device` or `function` are out of range.
+    pub fn new(domain: u32, bus: u8, device: u8, function: u8) -> Option<Self> {
+        if device > 0x1f || function > 0x7 {
+            return None;
+        }
+
+        Some(PciAddress {
+            domain,
+            bus,
+            device,
+            function,
+        })
+    }
+
+    /// This function returns the address in its long form, e.g. `0000:00:02.0`.
+    pub fn to_long_string(&self) -> String {
+        format!("{}", self)
+    }
+
+    /// This function returns the address in its short form, e.g. `00:02.0`, unless the domain isn't 0.
+    ///
+    /// This is the form `lspci` displays by default.
+    pub fn to_short_string(&self) -> String {
+        if self.domain == 0 {
+            format!("{:#}", self)
+        } else {
+            format!("{}", self)
+        }
+    }
+}
+
+impl fmt::Display for PciAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !f.alternate() {
+            write!(f, "{:04x}:", self.domain)?;
+        }
+        write!(
+            f,
+            "{:02x}:{:02x}.{:x}",
+            self.bus, self.device, self.function
+        )
+    }
+}
+
+impl FromStr for PciAddress {
+    type Err = Error;
+
+    /// This function accepts `BB:DD.F`, `DDDD:BB:DD.F` and paths ending with either of them.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidAddress(s.to_owned());
+        let address = s.trim_end_matches('/').rsplit('/').next().unwrap_or(s);
+
+        let mut fields: Vec<&str> = address.split(':').collect();
+        let domain = match fields.len() {
+            2 => 0,
+            3 => parse_field(fields.remove(0), 4, 8).ok_or_else(invalid)?,
+            _ => return Err(invalid()),
+        };
+        let bus = parse_field(fields[0], 2, 2).ok_or_else(invalid)?;
+        let (device, function) = fields[1].split_once('.').ok_or_else(invalid)?;
+        let device = parse_field(device, 2, 2).ok_or_else(invalid)?;
+        let function = parse_field(function, 1, 1).ok_or_else(invalid)?;
+
+        PciAddress::new(domain, bus as u8, device as u8, function as u8).ok_or_else(invalid)
+    }
+}
+
+/// This function parses a hexadecimal field made of `min` to `max` digits.
+fn parse_field(field: &str, min: usize, max: usize) -> Option<u32> {
+    if field.len() < min || field.len() > max || !field.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    u32::from_str_radix(field, 16).ok()
+}
+
+#[cfg(feature = "serde")]
+crate::serde_string::impl_serde_as_string!(PciAddress);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let address: PciAddress = "0001:3b:1f.7".parse().unwrap();
+        assert_eq!(address, PciAddress::new(1, 0x3b, 0x1f, 7).unwrap());
+        assert_eq!(address.to_long_string(), "0001:3b:1f.7");
+        assert_eq!(address.to_short_string(), "0001:3b:1f.7");
+
+        let address: PciAddress = "/sys/devices/pci0000:00/0000:00:1c.0/0000:02:00.0/"
+            .parse()
+            .unwrap();
+        assert_eq!(address.to_short_string(), "02:00.0");
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for s in &[
+            "",
+            "00:02",
+            "0:02.0",
+            "00:20.0",
+            "00:02.8",
+            "g000:00:02.0",
+            "00:02.0.1",
+        ] {
+            assert!(s.parse::<PciAddress>().is_err(), "{} was accepted", s);
+        }
+    }
+
+    #[test]
+    fn test_ordering() {
+        let mut addresses: Vec<PciAddress> = ["0001:00:00.0", "00:1f.3", "00:02.0", "00:02.1"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        addresses.sort();
+        let sorted: Vec<String> = addresses.iter().map(|a| a.to_string()).collect();
+        assert_eq!(
+            sorted,
+            [
+                "0000:00:02.0",
+                "0000:00:02.1",
+                "0000:00:1f.3",
+                "0001:00:00.0"
+            ]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let address: PciAddress = "10000:e1:00.1".parse().unwrap();
+        let json = serde_json::to_string(&address).unwrap();
+        assert_eq!(json, "\"10000:e1:00.1\"");
+        assert_eq!(serde_json::from_str::<PciAddress>(&json).unwrap(), address);
+        assert!(serde_json::from_str::<PciAddress>("\"00:02\"").is_err());
+    }
+}
@@ -0,0 +1,174 @@
+//! This module contains [Bar], a resource assigned to a PCI device through its base address registers.
+
+/// The index of the expansion ROM among the resources of a device.
+pub const ROM_INDEX: u8 = 6;
+
+// The resource flags of the kernel, see include/linux/ioport.h.
+const IORESOURCE_IO: u64 = 0x0000_0100;
+const IORESOURCE_MEM: u64 = 0x0000_0200;
+const IORESOURCE_PREFETCH: u64 = 0x0000_2000;
+const IORESOURCE_MEM_64: u64 = 0x0010_0000;
+const IORESOURCE_DISABLED: u64 = 0x1000_0000;
+const IORESOURCE_UNSET: u64 = 0x2000_0000;
+
+/// A resource decoded by a PCI device, either one of its 6 BARs or its expansion ROM.
+///
+/// # Examples
+///
+/// ```no_run
+/// use aparato::{Device, PCIDevice};
+///
+/// let gpu = PCIDevice::new("01:00.0");
+/// for bar in gpu.bars().unwrap() {
+///     println!("Region {}: {:?} at {:#x} [size={}K]", bar.index, bar.kind(), bar.start, bar.size() >> 10);
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Bar {
+    /// The index of the BAR, [ROM_INDEX] standing for the expansion ROM.
+    pub index: u8,
+    pub start: u64,
+    /// The last address of the resource, inclusive.
+    pub end: u64,
+    /// The resource flags of the kernel.
+    pub flags: u64,
+}
+
+/// The address space a [Bar] lives in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BarKind {
+    Io,
+    Memory,
+}
+
+impl Bar {
+    /// This function returns the size of the resource, in bytes.
+    ///
+    /// A resource spanning the whole 64-bit address space, whose size doesn't fit in a `u64`, saturates to `u64::MAX`.
+    pub fn size(&self) -> u64 {
+        match self.end.checked_sub(self.start) {
+            Some(size) if self.end != 0 => size.saturating_add(1),
+            _ => 0,
+        }
+    }
+
+    /// This function returns whether the resource lives in the I/O or the memory space.
+    pub fn kind(&self) -> BarKind {
+        if self.flags & IORESOURCE_IO != 0 {
+            BarKind::Io
+        } else {
+            BarKind::Memory
+        }
+    }
+
+    /// This function returns whether the resource can be placed above 4 GB.
+    pub fn is_64bit(&self) -> bool {
+        self.flags & IORESOURCE_MEM_64 != 0
+    }
+
+    /// This function returns whether reads of the resource have no side effects, which allows caching them.
+    pub fn is_prefetchable(&self) -> bool {
+        self.flags & IORESOURCE_PREFETCH != 0
+    }
+
+    /// This function returns whether the resource is the expansion ROM.
+    pub fn is_rom(&self) -> bool {
+        self.index == ROM_INDEX
+    }
+
+    /// This function returns whether the resource wasn't given an address, e.g. because its window was too small.
+    pub fn is_unassigned(&self) -> bool {
+        self.flags & IORESOURCE_UNSET != 0 || self.start == 0
+    }
+
+    /// This function returns whether the resource is disabled.
+    pub fn is_disabled(&self) -> bool {
+        self.flags & IORESOURCE_DISABLED != 0
+    }
+}
+
+/// This function parses the content of a sysfs `resource` file, which holds one resource per line,
+/// each made of its start, end and flags.
+///
+/// Only the BARs and the expansion ROM are returned, and only the implemented ones.
+pub(crate) fn parse_resource(content: &str) -> Option<Vec<Bar>> {
+    let mut bars = Vec::new();
+    for (index, line) in content.lines().enumerate().take(usize::from(ROM_INDEX) + 1) {
+        let fields: Vec<u64> = line
+            .split_whitespace()
+            .map(|field| u64::from_str_radix(field.trim_start_matches("0x"), 16).ok())
+            .collect::<Option<_>>()?;
+        let (start, end, flags) = match fields[..] {
+            [start, end, flags] => (start, end, flags),
+            _ => return None,
+        };
+
+        if flags & (IORESOURCE_IO | IORESOURCE_MEM) != 0 {
+            bars.push(Bar {
+                index: index as u8,
+                start,
+                end,
+                flags,
+            });
+        }
+    }
+    Some(bars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESOURCE: &str = "\
+0x00000000f6000000 0x00000000f6ffffff 0x0000000000040200
+0x00000000e0000000 0x00000000efffffff 0x000000000014220c
+0x0000000000000000 0x0000000000000000 0x0000000000000000
+0x0000000000000000 0x0000000001ffffff 0x000000002014220c
+0x0000000000000000 0x0000000000000000 0x0000000000000000
+0x000000000000e000 0x000000000000e07f 0x0000000000040101
+0x00000000f7000000 0x00000000f707ffff 0x0000000000046200
+0x0000000000000000 0x0000000000000000 0x0000000000000000
+";
+
+    #[test]
+    fn test_parse_resource() {
+        let bars = parse_resource(RESOURCE).unwrap();
+        let indices: Vec<u8> = bars.iter().map(|b| b.index).collect();
+        assert_eq!(indices, [0, 1, 3, 5, 6]);
+
+        assert_eq!(bars[0].kind(), BarKind::Memory);
+        assert_eq!(bars[0].size(), 16 << 20);
+        assert!(!bars[0].is_64bit() && !bars[0].is_prefetchable());
+
+        assert!(bars[1].is_64bit() && bars[1].is_prefetchable());
+        assert_eq!(bars[1].size(), 256 << 20);
+        assert!(!bars[1].is_unassigned());
+
+        assert!(bars[2].is_unassigned());
+        assert_eq!(bars[2].size(), 32 << 20);
+
+        assert_eq!(bars[3].kind(), BarKind::Io);
+        assert_eq!(bars[3].size(), 128);
+
+        assert!(bars[4].is_rom());
+        assert_eq!(bars[4].size(), 512 << 10);
+    }
+
+    #[test]
+    fn test_whole_address_space() {
+        let bar = Bar {
+            index: 0,
+            start: 0,
+            end: u64::MAX,
+            flags: IORESOURCE_MEM | IORESOURCE_MEM_64,
+        };
+        assert_eq!(bar.size(), u64::MAX);
+    }
+
+    #[test]
+    fn test_parse_invalid_resource() {
+        assert!(parse_resource("0x0 0x0").is_none());
+        assert!(parse_resource("0x0 0xzz 0x0").is_none());
+        assert_eq!(parse_resource("").unwrap(), []);
+    }
+}
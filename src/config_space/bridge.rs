@@ -0,0 +1,282 @@
+//! This module contains [BridgeInfo], the routing state of a PCI-to-PCI bridge.
+
+use super::{BridgeHeader, CapabilityKind, ConfigSpace, Header, PortType, Status};
+use std::fmt;
+
+/// The buses and address windows a PCI-to-PCI bridge forwards transactions to, decoded from its header.
+///
+/// # Examples
+///
+/// ```no_run
+/// use aparato::{Device, PCIDevice};
+///
+/// let bridge = PCIDevice::new("00:1c.0");
+/// if let Some(info) = bridge.bridge_info().unwrap() {
+///     println!("Bus: primary={:02x}, secondary={:02x}, subordinate={:02x}",
+///         info.primary_bus, info.secondary_bus, info.subordinate_bus);
+///     match info.memory_window {
+///         Some(window) => println!("Memory behind bridge: {}", window),
+///         None => println!("Memory behind bridge: [disabled]"),
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BridgeInfo {
+    /// The bus the bridge sits on.
+    pub primary_bus: u8,
+    /// The bus right behind the bridge.
+    pub secondary_bus: u8,
+    /// The highest bus behind the bridge.
+    pub subordinate_bus: u8,
+    /// The I/O window, `None` if it's disabled.
+    pub io_window: Option<Window>,
+    /// The non-prefetchable memory window, `None` if it's disabled.
+    pub memory_window: Option<Window>,
+    /// The prefetchable memory window, `None` if it's disabled.
+    pub prefetchable_window: Option<Window>,
+    /// Whether the prefetchable memory window can be placed above 4 GB.
+    pub prefetchable_64bit: bool,
+    /// The status of the secondary bus.
+    pub secondary_status: Status,
+    pub control: BridgeControl,
+    /// The role of the bridge in a PCI Express hierarchy, `None` for conventional PCI bridges
+    /// or when the capabilities couldn't be read.
+    pub port_type: Option<PortType>,
+}
+
+/// A range of addresses a bridge forwards to its secondary bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Window {
+    pub base: u64,
+    /// The last address of the window, inclusive.
+    pub limit: u64,
+}
+
+/// The bridge control register.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct BridgeControl(pub u16);
+
+impl BridgeInfo {
+    /// This function decodes the routing state of a bridge from its header and its PCI Express capability.
+    pub(crate) fn new(config: &ConfigSpace, header: &BridgeHeader) -> Self {
+        let port_type = config.capabilities().flatten().find_map(|c| match c.kind {
+            CapabilityKind::PciExpress(pcie) => Some(pcie.port_type()),
+            _ => None,
+        });
+
+        // The low nibble of the base registers tells whether the upper registers are implemented.
+        let io_32bit = header.io_base & 0xf == 1;
+        let io_upper = |upper: u16| if io_32bit { u64::from(upper) << 16 } else { 0 };
+        let io_window = Window::new(
+            u64::from(header.io_base & 0xf0) << 8 | io_upper(header.io_base_upper),
+            u64::from(header.io_limit & 0xf0) << 8 | 0xfff | io_upper(header.io_limit_upper),
+        );
+
+        let memory_window = Window::new(
+            u64::from(header.memory_base & 0xfff0) << 16,
+            u64::from(header.memory_limit & 0xfff0) << 16 | 0xf_ffff,
+        );
+
+        let prefetchable_64bit = header.prefetchable_memory_base & 0xf == 1;
+        let prefetchable_upper = |upper: u32| {
+            if prefetchable_64bit {
+                u64::from(upper) << 32
+            } else {
+                0
+            }
+        };
+        let prefetchable_window = Window::new(
+            u64::from(header.prefetchable_memory_base & 0xfff0) << 16
+                | prefetchable_upper(header.prefetchable_base_upper),
+            u64::from(header.prefetchable_memory_limit & 0xfff0) << 16
+                | 0xf_ffff
+                | prefetchable_upper(header.prefetchable_limit_upper),
+        );
+
+        BridgeInfo {
+            primary_bus: header.primary_bus,
+            secondary_bus: header.secondary_bus,
+            subordinate_bus: header.subordinate_bus,
+            io_window,
+            memory_window,
+            prefetchable_window,
+            prefetchable_64bit,
+            secondary_status: header.secondary_status,
+            control: BridgeControl(header.bridge_control),
+            port_type,
+        }
+    }
+}
+
+impl Window {
+    /// This function returns the window going from `base` to `limit`, or `None` if the window is disabled,
+    /// which bridges express with a base above the limit.
+    fn new(base: u64, limit: u64) -> Option<Self> {
+        Some(Window { base, limit }).filter(|_| base <= limit)
+    }
+
+    /// This function returns the size of the window, in bytes, or `None` if the window spans
+    /// the whole 64-bit address space, whose size doesn't fit in a `u64`.
+    pub fn size(&self) -> Option<u64> {
+        (self.limit - self.base).checked_add(1)
+    }
+
+    /// This function returns whether `address` falls within the window.
+    pub fn contains(&self, address: u64) -> bool {
+        (self.base..=self.limit).contains(&address)
+    }
+}
+
+impl BridgeControl {
+    /// This function returns whether parity errors on the secondary bus are reported.
+    pub fn parity_error_response(&self) -> bool {
+        self.0 & 1 << 0 != 0
+    }
+
+    /// This function returns whether system errors on the secondary bus are forwarded to the primary bus.
+    pub fn serr(&self) -> bool {
+        self.0 & 1 << 1 != 0
+    }
+
+    /// This function returns whether I/O accesses to the aliases of ISA addresses are blocked.
+    pub fn isa(&self) -> bool {
+        self.0 & 1 << 2 != 0
+    }
+
+    /// This function returns whether the legacy VGA ranges are forwarded to the secondary bus.
+    pub fn vga(&self) -> bool {
+        self.0 & 1 << 3 != 0
+    }
+
+    /// This function returns whether only 16 bits of the VGA I/O addresses are decoded.
+    pub fn vga_16bit(&self) -> bool {
+        self.0 & 1 << 4 != 0
+    }
+
+    /// This function returns whether master aborts are reported.
+    pub fn master_abort(&self) -> bool {
+        self.0 & 1 << 5 != 0
+    }
+
+    /// This function returns whether the secondary bus is held in reset.
+    pub fn secondary_bus_reset(&self) -> bool {
+        self.0 & 1 << 6 != 0
+    }
+
+    /// This function returns whether fast back-to-back transactions are enabled on the secondary bus.
+    pub fn fast_back_to_back(&self) -> bool {
+        self.0 & 1 << 7 != 0
+    }
+}
+
+impl ConfigSpace {
+    /// This function returns the routing state of the device if it's a PCI-to-PCI bridge, or `None` otherwise.
+    pub fn bridge_info(&self) -> Option<BridgeInfo> {
+        match self.header() {
+            Header::Bridge(header) => Some(BridgeInfo::new(self, &header)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Window {
+    /// This function displays the window the way `lspci -v` does, e.g. `f7000000-f70fffff [size=1M]`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // The size is computed on 128 bits, since it reaches 2^64 for a window spanning the whole address space.
+        let size = u128::from(self.limit - self.base) + 1;
+        let (value, unit) = match size.trailing_zeros() {
+            30.. => (size >> 30, "G"),
+            20..=29 => (size >> 20, "M"),
+            10..=19 => (size >> 10, "K"),
+            _ => (size, ""),
+        };
+        write!(
+            f,
+            "{:08x}-{:08x} [size={}{}]",
+            self.base, self.limit, value, unit
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{bridge, endpoint};
+    use super::*;
+
+    #[test]
+    fn test_bridge_info() {
+        let config = ConfigSpace::new(bridge()).unwrap();
+        let info = config.bridge_info().unwrap();
+        assert_eq!(
+            (info.primary_bus, info.secondary_bus, info.subordinate_bus),
+            (0x00, 0x02, 0x03)
+        );
+        // The I/O base is above the limit.
+        assert_eq!(info.io_window, None);
+        assert_eq!(
+            info.memory_window,
+            Some(Window {
+                base: 0xf700_0000,
+                limit: 0xf70f_ffff,
+            })
+        );
+        assert_eq!(
+            info.memory_window.unwrap().to_string(),
+            "f7000000-f70fffff [size=1M]"
+        );
+        assert!(info.control.vga_16bit() && !info.control.vga());
+        // The capability list holds no PCI Express capability.
+        assert_eq!(info.port_type, None);
+
+        assert_eq!(ConfigSpace::new(endpoint()).unwrap().bridge_info(), None);
+    }
+
+    #[test]
+    fn test_pcie_bridge_info() {
+        let mut data = bridge();
+        // 32-bit I/O window at 0x1_2000-0x1_2fff.
+        data[0x1c..0x1e].copy_from_slice(&[0x21, 0x21]);
+        data[0x30..0x34].copy_from_slice(&[0x01, 0x00, 0x01, 0x00]);
+        // 64-bit prefetchable window at 0x40_0000_0000-0x40_1fff_ffff.
+        data[0x24..0x28].copy_from_slice(&[0x01, 0x00, 0xf1, 0x1f]);
+        data[0x28..0x30].copy_from_slice(&[0x40, 0, 0, 0, 0x40, 0, 0, 0]);
+        data[0x3e..0x40].copy_from_slice(&0x004bu16.to_le_bytes());
+        // A PCI Express capability describing a downstream switch port.
+        data[0x40..0x44].copy_from_slice(&[0x10, 0x00, 0x62, 0x00]);
+        let info = ConfigSpace::new(data).unwrap().bridge_info().unwrap();
+
+        assert_eq!(
+            info.io_window,
+            Some(Window {
+                base: 0x1_2000,
+                limit: 0x1_2fff,
+            })
+        );
+        assert!(info.prefetchable_64bit);
+        let prefetchable = info.prefetchable_window.unwrap();
+        assert_eq!(prefetchable.base, 0x40_0000_0000);
+        assert_eq!(prefetchable.size(), Some(512 << 20));
+        assert!(prefetchable.contains(0x40_1000_0000));
+        assert!(info.control.parity_error_response() && info.control.serr());
+        assert!(info.control.vga() && info.control.secondary_bus_reset());
+        assert_eq!(info.port_type, Some(PortType::DownstreamPort));
+    }
+
+    #[test]
+    fn test_whole_address_space_window() {
+        let mut data = bridge();
+        // 64-bit prefetchable window at 0-0xffff_ffff_ffff_ffff.
+        data[0x24..0x28].copy_from_slice(&[0x01, 0x00, 0xf1, 0xff]);
+        data[0x28..0x30].copy_from_slice(&[0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff]);
+        let info = ConfigSpace::new(data).unwrap().bridge_info().unwrap();
+
+        let window = info.prefetchable_window.unwrap();
+        assert_eq!((window.base, window.limit), (0, u64::MAX));
+        assert_eq!(window.size(), None);
+        assert!(window.contains(u64::MAX));
+        assert_eq!(
+            window.to_string(),
+            "00000000-ffffffffffffffff [size=17179869184G]"
+        );
+    }
+}
@@ -0,0 +1,587 @@
+//! This module contains the walker of the standard capability list, which `lspci -v` displays.
+
+use super::ConfigSpace;
+use crate::error::{Error, Result};
+use crate::ids::{DeviceId, SubsystemId, VendorId};
+
+/// A capability found in the standard capability list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capability {
+    /// The offset of the capability within the configuration space.
+    pub offset: u8,
+    /// The ID of the capability, e.g. `0x10` for PCI Express.
+    pub id: u8,
+    pub kind: CapabilityKind,
+}
+
+/// The decoded body of a capability.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CapabilityKind {
+    PowerManagement(PowerManagement),
+    Vpd(Vpd),
+    Msi(Msi),
+    VendorSpecific(VendorSpecific),
+    /// The subsystem of a bridge, whose header has no room for it.
+    BridgeSubsystem(SubsystemId),
+    PciExpress(PciExpress),
+    MsiX(MsiX),
+    /// A capability which isn't decoded, its ID is held by [`Capability::id`].
+    Other,
+}
+
+/// The power management capability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerManagement {
+    /// The raw power management capabilities register.
+    pub capabilities: u16,
+    /// The raw power management control and status register.
+    pub control_status: u16,
+}
+
+/// The vital product data capability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Vpd {
+    pub address: u16,
+    pub data: u32,
+}
+
+/// The message signaled interrupts capability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Msi {
+    /// The raw message control register.
+    pub control: u16,
+    pub address: u64,
+    pub data: u16,
+    /// The mask bits, present when per-vector masking is supported.
+    pub mask: Option<u32>,
+    /// The pending bits, present when per-vector masking is supported.
+    pub pending: Option<u32>,
+}
+
+/// The MSI-X capability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MsiX {
+    /// The raw message control register.
+    pub control: u16,
+    /// The raw table offset and BAR indicator register.
+    pub table: u32,
+    /// The raw pending bit array offset and BAR indicator register.
+    pub pba: u32,
+}
+
+/// A vendor-specific capability, whose layout is only known to the vendor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VendorSpecific {
+    /// The bytes following the header of the capability.
+    pub data: Vec<u8>,
+}
+
+/// The PCI Express capability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PciExpress {
+    /// The raw PCI Express capabilities register.
+    pub capabilities: u16,
+    pub device_capabilities: u32,
+    pub device_control: u16,
+    pub device_status: u16,
+    pub link_capabilities: u32,
+    pub link_control: u16,
+    pub link_status: u16,
+    /// The second link capabilities register, only present since version 2 of the capability.
+    pub link_capabilities2: Option<u32>,
+}
+
+/// The role of a PCI Express function in the hierarchy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PortType {
+    Endpoint,
+    LegacyEndpoint,
+    RootPort,
+    /// The upstream port of a switch.
+    UpstreamPort,
+    /// A downstream port of a switch.
+    DownstreamPort,
+    PcieToPciBridge,
+    PciToPcieBridge,
+    RootComplexIntegratedEndpoint,
+    RootComplexEventCollector,
+    /// A value the specification reserves.
+    Reserved(u8),
+}
+
+/// An iterator over the standard capability list of a configuration space,
+/// returned by [`ConfigSpace::capabilities()`].
+///
+/// The iterator stops after returning an error, such as a pointer which was already followed.
+pub struct Capabilities<'a> {
+    config: &'a ConfigSpace,
+    next: u8,
+    // One bit per double word of the conventional configuration space.
+    visited: u64,
+}
+
+impl<'a> Capabilities<'a> {
+    pub(super) fn new(config: &'a ConfigSpace) -> Self {
+        let next = if config.status().capabilities_list() {
+            // The type 2 header of CardBus bridges stores the pointer elsewhere.
+            let pointer = if config.header_type().layout() == 2 {
+                0x14
+            } else {
+                0x34
+            };
+            config.as_bytes()[pointer]
+        } else {
+            0
+        };
+
+        Capabilities {
+            config,
+            next,
+            visited: 0,
+        }
+    }
+
+    fn read(&mut self, offset: u8) -> Result<Capability> {
+        if offset < 0x40 {
+            return Err(malformed(offset, "the capability overlaps the header"));
+        }
+        let bit = 1 << (offset / 4);
+        if self.visited & bit != 0 {
+            return Err(malformed(offset, "the capability list loops"));
+        }
+        self.visited |= bit;
+
+        let config = self.config;
+        let offset = usize::from(offset);
+        let id = config.read_u8(offset)?;
+        self.next = config.read_u8(offset + 1)? & 0xfc;
+
+        let kind = match id {
+            0x01 => CapabilityKind::PowerManagement(PowerManagement {
+                capabilities: config.read_u16(offset + 2)?,
+                control_status: config.read_u16(offset + 4)?,
+            }),
+            0x03 => CapabilityKind::Vpd(Vpd {
+                address: config.read_u16(offset + 2)?,
+                data: config.read_u32(offset + 4)?,
+            }),
+            0x05 => CapabilityKind::Msi(read_msi(config, offset)?),
+            0x09 => {
+                let length = usize::from(config.read_u8(offset + 2)?);
+                let end = offset + length.max(3);
+                match config.as_bytes().get(offset + 3..end) {
+                    Some(data) => CapabilityKind::VendorSpecific(VendorSpecific {
+                        data: data.to_vec(),
+                    }),
+                    None => {
+                        return Err(Error::TruncatedConfigSpace {
+                            offset: end,
+                            len: config.len(),
+                        })
+                    }
+                }
+            }
+            0x0d => CapabilityKind::BridgeSubsystem(SubsystemId::new(
+                VendorId(config.read_u16(offset + 4)?),
+                DeviceId(config.read_u16(offset + 6)?),
+            )),
+            0x10 => {
+                let capabilities = config.read_u16(offset + 2)?;
+                CapabilityKind::PciExpress(PciExpress {
+                    capabilities,
+                    device_capabilities: config.read_u32(offset + 0x04)?,
+                    device_control: config.read_u16(offset + 0x08)?,
+                    device_status: config.read_u16(offset + 0x0a)?,
+                    link_capabilities: config.read_u32(offset + 0x0c)?,
+                    link_control: config.read_u16(offset + 0x10)?,
+                    link_status: config.read_u16(offset + 0x12)?,
+                    link_capabilities2: match capabilities & 0xf {
+                        v if v >= 2 => Some(config.read_u32(offset + 0x2c)?),
+                        _ => None,
+                    },
+                })
+            }
+            0x11 => CapabilityKind::MsiX(MsiX {
+                control: config.read_u16(offset + 2)?,
+                table: config.read_u32(offset + 4)?,
+                pba: config.read_u32(offset + 8)?,
+            }),
+            _ => CapabilityKind::Other,
+        };
+
+        Ok(Capability {
+            offset: offset as u8,
+            id,
+            kind,
+        })
+    }
+}
+
+impl Iterator for Capabilities<'_> {
+    type Item = Result<Capability>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.next & 0xfc;
+        if offset == 0 {
+            return None;
+        }
+
+        let capability = self.read(offset);
+        if capability.is_err() {
+            self.next = 0;
+        }
+        Some(capability)
+    }
+}
+
+impl Capability {
+    /// This function returns the name `lspci` gives to the capability, e.g. `MSI-X`.
+    pub fn name(&self) -> &'static str {
+        match self.id {
+            0x01 => "Power Management",
+            0x02 => "AGP",
+            0x03 => "Vital Product Data",
+            0x04 => "Slot Identification",
+            0x05 => "MSI",
+            0x06 => "CompactPCI hot-swap",
+            0x07 => "PCI-X",
+            0x08 => "HyperTransport",
+            0x09 => "Vendor Specific Information",
+            0x0a => "Debug port",
+            0x0b => "CompactPCI central resource control",
+            0x0c => "PCI Standard Hot-Plug Controller",
+            0x0d => "Subsystem",
+            0x0e => "AGP3",
+            0x0f => "Secure device",
+            0x10 => "Express",
+            0x11 => "MSI-X",
+            0x12 => "SATA HBA",
+            0x13 => "PCI Advanced Features",
+            0x14 => "Enhanced Allocation",
+            0x15 => "Flattening Portal Bridge",
+            _ => "Unknown",
+        }
+    }
+}
+
+impl PowerManagement {
+    /// This function returns the version of the power management specification the device complies with.
+    pub fn version(&self) -> u8 {
+        (self.capabilities & 0x7) as u8
+    }
+
+    /// This function returns whether the device supports the D1 power state.
+    pub fn d1_support(&self) -> bool {
+        self.capabilities & 1 << 9 != 0
+    }
+
+    /// This function returns whether the device supports the D2 power state.
+    pub fn d2_support(&self) -> bool {
+        self.capabilities & 1 << 10 != 0
+    }
+
+    /// This function returns the power states from which the device can assert PME#, one bit per state from D0 to D3cold.
+    pub fn pme_support(&self) -> u8 {
+        (self.capabilities >> 11) as u8
+    }
+
+    /// This function returns the current power state, 0 for D0 through 3 for D3hot.
+    pub fn power_state(&self) -> u8 {
+        (self.control_status & 0x3) as u8
+    }
+
+    /// This function returns whether the device keeps its state when going from D3hot to D0.
+    pub fn no_soft_reset(&self) -> bool {
+        self.control_status & 1 << 3 != 0
+    }
+}
+
+impl Msi {
+    /// This function returns whether MSI is enabled.
+    pub fn enabled(&self) -> bool {
+        self.control & 1 != 0
+    }
+
+    /// This function returns the number of vectors the device can use.
+    pub fn vectors_capable(&self) -> u8 {
+        1u8 << (self.control >> 1 & 0x7)
+    }
+
+    /// This function returns the number of vectors the device was allotted.
+    pub fn vectors_enabled(&self) -> u8 {
+        1u8 << (self.control >> 4 & 0x7)
+    }
+
+    /// This function returns whether the device can use 64-bit message addresses.
+    pub fn is_64bit(&self) -> bool {
+        self.control & 1 << 7 != 0
+    }
+
+    /// This function returns whether the vectors can be masked individually.
+    pub fn per_vector_masking(&self) -> bool {
+        self.control & 1 << 8 != 0
+    }
+}
+
+impl MsiX {
+    /// This function returns whether MSI-X is enabled.
+    pub fn enabled(&self) -> bool {
+        self.control & 1 << 15 != 0
+    }
+
+    /// This function returns whether every vector is masked.
+    pub fn function_mask(&self) -> bool {
+        self.control & 1 << 14 != 0
+    }
+
+    /// This function returns the number of entries of the MSI-X table.
+    pub fn table_size(&self) -> u16 {
+        (self.control & 0x7ff) + 1
+    }
+
+    /// This function returns the index of the BAR which holds the MSI-X table.
+    pub fn table_bar(&self) -> u8 {
+        (self.table & 0x7) as u8
+    }
+
+    /// This function returns the offset of the MSI-X table within its BAR.
+    pub fn table_offset(&self) -> u32 {
+        self.table & !0x7
+    }
+
+    /// This function returns the index of the BAR which holds the pending bit array.
+    pub fn pba_bar(&self) -> u8 {
+        (self.pba & 0x7) as u8
+    }
+
+    /// This function returns the offset of the pending bit array within its BAR.
+    pub fn pba_offset(&self) -> u32 {
+        self.pba & !0x7
+    }
+}
+
+impl PciExpress {
+    /// This function returns the version of the capability.
+    pub fn version(&self) -> u8 {
+        (self.capabilities & 0xf) as u8
+    }
+
+    /// This function returns the role of the function in the PCI Express hierarchy.
+    pub fn port_type(&self) -> PortType {
+        PortType::from((self.capabilities >> 4 & 0xf) as u8)
+    }
+
+    /// This function returns whether the port is connected to a slot.
+    pub fn slot_implemented(&self) -> bool {
+        self.capabilities & 1 << 8 != 0
+    }
+
+    /// This function returns the largest payload the device supports, in bytes.
+    pub fn max_payload_size_supported(&self) -> u16 {
+        128 << (self.device_capabilities & 0x7)
+    }
+
+    /// This function returns the largest payload the device is allowed to use, in bytes.
+    pub fn max_payload_size(&self) -> u16 {
+        128 << (self.device_control >> 5 & 0x7)
+    }
+
+    /// This function returns the largest read request the device is allowed to issue, in bytes.
+    pub fn max_read_request_size(&self) -> u16 {
+        128 << (self.device_control >> 12 & 0x7)
+    }
+
+    /// This function returns the encoded maximum link speed, 1 for 2.5 GT/s, 2 for 5 GT/s and so on.
+    pub fn max_link_speed(&self) -> u8 {
+        (self.link_capabilities & 0xf) as u8
+    }
+
+    /// This function returns the maximum link width, in lanes.
+    pub fn max_link_width(&self) -> u8 {
+        (self.link_capabilities >> 4 & 0x3f) as u8
+    }
+
+    /// This function returns the encoded current link speed, 1 for 2.5 GT/s, 2 for 5 GT/s and so on.
+    pub fn current_link_speed(&self) -> u8 {
+        (self.link_status & 0xf) as u8
+    }
+
+    /// This function returns the negotiated link width, in lanes.
+    pub fn negotiated_link_width(&self) -> u8 {
+        (self.link_status >> 4 & 0x3f) as u8
+    }
+}
+
+impl From<u8> for PortType {
+    fn from(port_type: u8) -> Self {
+        match port_type {
+            0x0 => PortType::Endpoint,
+            0x1 => PortType::LegacyEndpoint,
+            0x4 => PortType::RootPort,
+            0x5 => PortType::UpstreamPort,
+            0x6 => PortType::DownstreamPort,
+            0x7 => PortType::PcieToPciBridge,
+            0x8 => PortType::PciToPcieBridge,
+            0x9 => PortType::RootComplexIntegratedEndpoint,
+            0xa => PortType::RootComplexEventCollector,
+            port_type => PortType::Reserved(port_type),
+        }
+    }
+}
+
+fn read_msi(config: &ConfigSpace, offset: usize) -> Result<Msi> {
+    let control = config.read_u16(offset + 2)?;
+    let mut msi = Msi {
+        control,
+        address: u64::from(config.read_u32(offset + 4)?),
+        data: 0,
+        mask: None,
+        pending: None,
+    };
+
+    // The message data and the mask bits move past the upper half of the address when it exists.
+    let mut next = offset + 8;
+    if msi.is_64bit() {
+        msi.address |= u64::from(config.read_u32(next)?) << 32;
+        next += 4;
+    }
+    msi.data = config.read_u16(next)?;
+    if msi.per_vector_masking() {
+        msi.mask = Some(config.read_u32(next + 4)?);
+        msi.pending = Some(config.read_u32(next + 8)?);
+    }
+
+    Ok(msi)
+}
+
+fn malformed(offset: u8, reason: &str) -> Error {
+    Error::MalformedCapability {
+        offset: usize::from(offset),
+        reason: reason.to_owned(),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::super::tests::endpoint;
+    use super::*;
+
+    /// This function returns the configuration space of an endpoint with PM, MSI, PCIe and MSI-X capabilities.
+    pub(crate) fn with_capabilities() -> Vec<u8> {
+        let mut data = endpoint();
+        data[0x34] = 0x40;
+        // Power management, in D3hot.
+        data[0x40..0x46].copy_from_slice(&[0x01, 0x50, 0x03, 0xc6, 0x03, 0x00]);
+        // 64-bit MSI with per-vector masking, 4 vectors capable.
+        data[0x50..0x54].copy_from_slice(&[0x05, 0x70, 0x85, 0x01]);
+        data[0x54..0x58].copy_from_slice(&0xfee0_0000u32.to_le_bytes());
+        data[0x5c..0x5e].copy_from_slice(&0x4021u16.to_le_bytes());
+        // PCIe v2 endpoint, 16 GT/s x16 capable, running at 8 GT/s x8.
+        data[0x70..0x74].copy_from_slice(&[0x10, 0xb0, 0x02, 0x00]);
+        data[0x74..0x78].copy_from_slice(&0x0000_0002u32.to_le_bytes());
+        data[0x78..0x7a].copy_from_slice(&0x2010u16.to_le_bytes());
+        data[0x7c..0x80].copy_from_slice(&0x0000_0104u32.to_le_bytes());
+        data[0x82..0x84].copy_from_slice(&0x0083u16.to_le_bytes());
+        data[0x9c..0xa0].copy_from_slice(&0x0000_001eu32.to_le_bytes());
+        // MSI-X, 64 entries in BAR 0.
+        data[0xb0..0xb4].copy_from_slice(&[0x11, 0x00, 0x3f, 0x80]);
+        data[0xb4..0xb8].copy_from_slice(&0x0000_2000u32.to_le_bytes());
+        data[0xb8..0xbc].copy_from_slice(&0x0000_3000u32.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let config = ConfigSpace::new(with_capabilities()).unwrap();
+        let capabilities: Vec<Capability> = config.capabilities().map(|c| c.unwrap()).collect();
+
+        let offsets: Vec<(u8, &str)> = capabilities.iter().map(|c| (c.offset, c.name())).collect();
+        assert_eq!(
+            offsets,
+            [
+                (0x40, "Power Management"),
+                (0x50, "MSI"),
+                (0x70, "Express"),
+                (0xb0, "MSI-X")
+            ]
+        );
+
+        match &capabilities[0].kind {
+            CapabilityKind::PowerManagement(pm) => {
+                assert_eq!(pm.version(), 3);
+                assert_eq!(pm.power_state(), 3);
+            }
+            kind => panic!("unexpected capability: {:?}", kind),
+        }
+        match &capabilities[1].kind {
+            CapabilityKind::Msi(msi) => {
+                assert!(msi.enabled() && msi.is_64bit() && msi.per_vector_masking());
+                assert_eq!(msi.vectors_capable(), 4);
+                assert_eq!(msi.address, 0xfee0_0000);
+                assert_eq!(msi.data, 0x4021);
+                assert_eq!(msi.mask, Some(0));
+            }
+            kind => panic!("unexpected capability: {:?}", kind),
+        }
+        match &capabilities[2].kind {
+            CapabilityKind::PciExpress(pcie) => {
+                assert_eq!(pcie.port_type(), PortType::Endpoint);
+                assert_eq!(pcie.max_payload_size_supported(), 512);
+                assert_eq!(pcie.max_read_request_size(), 512);
+                assert_eq!((pcie.max_link_speed(), pcie.max_link_width()), (4, 16));
+                assert_eq!(
+                    (pcie.current_link_speed(), pcie.negotiated_link_width()),
+                    (3, 8)
+                );
+                assert_eq!(pcie.link_capabilities2, Some(0x1e));
+            }
+            kind => panic!("unexpected capability: {:?}", kind),
+        }
+        match &capabilities[3].kind {
+            CapabilityKind::MsiX(msix) => {
+                assert!(msix.enabled());
+                assert_eq!(msix.table_size(), 64);
+                assert_eq!((msix.table_bar(), msix.table_offset()), (0, 0x2000));
+            }
+            kind => panic!("unexpected capability: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn test_loop() {
+        let mut data = with_capabilities();
+        // The MSI-X capability points back to the MSI capability.
+        data[0xb1] = 0x50;
+        let config = ConfigSpace::new(data).unwrap();
+
+        let results: Vec<Result<Capability>> = config.capabilities().collect();
+        assert_eq!(results.len(), 5);
+        assert!(matches!(
+            results[4],
+            Err(Error::MalformedCapability { offset: 0x50, .. })
+        ));
+    }
+
+    #[test]
+    fn test_truncated() {
+        let mut data = with_capabilities();
+        data.truncate(64);
+        let config = ConfigSpace::new(data).unwrap();
+
+        let results: Vec<Result<Capability>> = config.capabilities().collect();
+        assert_eq!(results.len(), 1);
+        assert!(matches!(
+            results[0],
+            Err(Error::TruncatedConfigSpace { offset: 0x40, .. })
+        ));
+    }
+
+    #[test]
+    fn test_no_capabilities() {
+        let mut data = with_capabilities();
+        // Clear the capabilities list bit of the status register.
+        data[0x06] &= !0x10;
+        let config = ConfigSpace::new(data).unwrap();
+        assert_eq!(config.capabilities().count(), 0);
+    }
+}
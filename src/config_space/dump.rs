@@ -0,0 +1,345 @@
+//! This module contains the parser of configuration space dumps, which allows decoding devices offline.
+//!
+//! Both the hex dumps printed by `lspci -x`, `-xxx` and `-xxxx`, and raw binary blobs,
+//! such as a copy of a sysfs `config` file, are supported.
+
+use super::{ConfigSpace, EXTENDED_SIZE};
+use crate::address::PciAddress;
+use crate::error::{Error, Result};
+use crate::pci_ids::PciIds;
+
+/// A device decoded from a configuration space dump.
+///
+/// # Examples
+///
+/// ```
+/// use aparato::config_space::Dump;
+///
+/// let output = "\
+/// 00:14.0 USB controller: Intel Corporation Device a3af
+/// 00: 86 80 af a3 06 04 90 02 00 30 03 0c 00 00 80 00
+/// 10: 04 00 f2 f7 00 00 00 00 00 00 00 00 00 00 00 00
+/// 20: 00 00 00 00 00 00 00 00 00 00 00 00 aa 17 92 22
+/// 30: 00 00 00 00 70 00 00 00 00 00 00 00 ff 01 00 00
+/// ";
+///
+/// let dumps = Dump::parse_lspci(output).unwrap();
+/// assert_eq!(dumps[0].address.unwrap().to_short_string(), "00:14.0");
+/// assert_eq!(dumps[0].config.class_code().to_string(), "0c0330");
+/// assert!(dumps[0].config.is_truncated());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dump {
+    /// The address of the device, which raw binary blobs don't record.
+    pub address: Option<PciAddress>,
+    /// The description `lspci` printed after the address, e.g. `USB controller: Intel Corporation ...`.
+    pub description: Option<String>,
+    pub config: ConfigSpace,
+}
+
+impl Dump {
+    /// This function returns a new `Dump` from a raw binary blob, e.g. a copy of a sysfs `config` file.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        Ok(Dump {
+            address: None,
+            description: None,
+            config: ConfigSpace::new(data)?,
+        })
+    }
+
+    /// This function parses the output of `lspci -x`, `-xxx` or `-xxxx`, which may hold several devices.
+    ///
+    /// The details printed along with `-v`, `-vv` or `-vvv`, e.g. by `lspci -vvxxx`, are skipped.
+    /// A hex dump which isn't preceded by a device line, e.g. `00: 86 80 ...`, is accepted as well.
+    pub fn parse_lspci(s: &str) -> Result<Vec<Self>> {
+        let mut dumps = Vec::new();
+        let mut current: Option<Partial> = None;
+
+        for (index, line) in s.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim_end();
+            if line.is_empty() {
+                continue;
+            }
+            // The details printed by `lspci -v`, `-vv` and `-vvv` are indented below the device line.
+            if current.is_some() && line.starts_with(char::is_whitespace) {
+                continue;
+            }
+
+            if let Some((offset, bytes)) = parse_hex_line(line, line_number)? {
+                let partial = current.get_or_insert_with(|| Partial::new(None, None, line_number));
+                partial.push(offset, bytes, line_number)?;
+                continue;
+            }
+
+            // Any other line introduces a new device, e.g. `00:14.0 USB controller: ...`.
+            let (address, description) = match line.split_once(' ') {
+                Some((address, description)) => (address, Some(description.to_owned())),
+                None => (line, None),
+            };
+            let address = match address.parse() {
+                Ok(address) => address,
+                // Other lines lspci may print within a device, e.g. the `Capabilities:` line
+                // of `lspci -vv` when the configuration space can't be read, are skipped.
+                Err(_) if current.is_some() => continue,
+                Err(_) => {
+                    return Err(Error::InvalidDump {
+                        line: line_number,
+                        reason: format!("{:?} is neither a device nor a hex dump", line),
+                    })
+                }
+            };
+
+            if let Some(partial) = current.take() {
+                dumps.push(partial.finish()?);
+            }
+            current = Some(Partial::new(Some(address), description, line_number));
+        }
+
+        if let Some(partial) = current {
+            dumps.push(partial.finish()?);
+        }
+        Ok(dumps)
+    }
+
+    /// This function returns the name of the vendor of the device.
+    pub fn vendor_name<'a>(&self, ids: &'a PciIds) -> Option<&'a str> {
+        ids.vendor_name(self.config.vendor_id().0)
+    }
+
+    /// This function returns the name of the device.
+    pub fn device_name<'a>(&self, ids: &'a PciIds) -> Option<&'a str> {
+        ids.device_name(self.config.vendor_id().0, self.config.device_id().0)
+    }
+
+    /// This function returns the name of the subsystem of the device.
+    pub fn subsystem_name<'a>(&self, ids: &'a PciIds) -> Option<&'a str> {
+        let subsystem = self.config.subsystem()?;
+        ids.subsystem_name(
+            self.config.vendor_id().0,
+            self.config.device_id().0,
+            subsystem.vendor.0,
+            subsystem.device.0,
+        )
+    }
+
+    /// This function returns the name of the class of the device.
+    pub fn class_name<'a>(&self, ids: &'a PciIds) -> Option<&'a str> {
+        ids.class_name(self.config.class_code().class)
+    }
+
+    /// This function returns the name of the subclass of the device.
+    pub fn subclass_name<'a>(&self, ids: &'a PciIds) -> Option<&'a str> {
+        let class = self.config.class_code();
+        ids.subclass_name(class.class, class.subclass)
+    }
+
+    /// This function returns the name of the programming interface of the device.
+    pub fn prog_if_name<'a>(&self, ids: &'a PciIds) -> Option<&'a str> {
+        let class = self.config.class_code();
+        ids.prog_if_name(class.class, class.subclass, class.prog_if)
+    }
+}
+
+/// A dump whose hex lines are still being read.
+struct Partial {
+    address: Option<PciAddress>,
+    description: Option<String>,
+    data: Vec<u8>,
+    line: usize,
+}
+
+impl Partial {
+    fn new(address: Option<PciAddress>, description: Option<String>, line: usize) -> Self {
+        Partial {
+            address,
+            description,
+            data: Vec::new(),
+            line,
+        }
+    }
+
+    fn push(&mut self, offset: usize, bytes: Vec<u8>, line: usize) -> Result<()> {
+        // lspci prints every line, a gap means the dump was cut or edited.
+        if offset != self.data.len() || offset + bytes.len() > EXTENDED_SIZE {
+            return Err(Error::InvalidDump {
+                line,
+                reason: format!("expected offset {:x}, found {:x}", self.data.len(), offset),
+            });
+        }
+        self.data.extend(bytes);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Dump> {
+        let line = self.line;
+        let config = ConfigSpace::new(self.data).map_err(|_| Error::InvalidDump {
+            line,
+            reason: "the dump doesn't cover the 64-byte header".to_owned(),
+        })?;
+
+        Ok(Dump {
+            address: self.address,
+            description: self.description,
+            config,
+        })
+    }
+}
+
+/// This function parses a line such as `10: 04 00 f2 f7 ...`, it returns `None` for any other line.
+fn parse_hex_line(line: &str, line_number: usize) -> Result<Option<(usize, Vec<u8>)>> {
+    let (offset, bytes) = match line.split_once(": ") {
+        Some(fields) => fields,
+        None => return Ok(None),
+    };
+    // Device lines start with an address, whose fields are separated by colons too.
+    if offset.is_empty() || !offset.bytes().all(|b| b.is_ascii_hexdigit()) || offset.len() > 3 {
+        return Ok(None);
+    }
+    let offset =
+        usize::from_str_radix(offset, 16).map_err(|_| invalid_byte(line_number, offset))?;
+
+    let bytes = bytes
+        .split_whitespace()
+        .map(|byte| match byte.len() {
+            2 => u8::from_str_radix(byte, 16).map_err(|_| invalid_byte(line_number, byte)),
+            _ => Err(invalid_byte(line_number, byte)),
+        })
+        .collect::<Result<Vec<u8>>>()?;
+    Ok(Some((offset, bytes)))
+}
+
+fn invalid_byte(line: usize, byte: &str) -> Error {
+    Error::InvalidDump {
+        line,
+        reason: format!("{:?} isn't a hexadecimal byte", byte),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::extended::tests::with_extended_capabilities;
+    use super::super::{CapabilityKind, ExtendedCapabilityKind};
+    use super::*;
+
+    /// This function formats `data` the way `lspci -x` does.
+    fn hex_dump(data: &[u8]) -> String {
+        let mut dump = String::new();
+        for (i, line) in data.chunks(16).enumerate() {
+            let bytes: Vec<String> = line.iter().map(|b| format!("{:02x}", b)).collect();
+            dump.push_str(&format!("{:02x}: {}\n", i * 16, bytes.join(" ")));
+        }
+        dump
+    }
+
+    #[test]
+    fn test_parse_lspci() {
+        let data = with_extended_capabilities();
+        let output = format!(
+            "0000:00:14.0 USB controller: Intel Corporation Device a3ed\n{}\n\
+             00:02.0 VGA compatible controller: Intel Corporation Device 1234\n{}\n",
+            hex_dump(&data),
+            hex_dump(&data[..256]),
+        );
+
+        let dumps = Dump::parse_lspci(&output).unwrap();
+        assert_eq!(dumps.len(), 2);
+        assert_eq!(dumps[0].address, "00:14.0".parse().ok());
+        assert_eq!(
+            dumps[0].description.as_deref(),
+            Some("USB controller: Intel Corporation Device a3ed")
+        );
+        assert_eq!(dumps[0].config.as_bytes(), &data[..]);
+        assert_eq!(dumps[1].config.len(), 256);
+
+        // The same model is built as from sysfs.
+        let capabilities = dumps[0].config.capabilities().count();
+        assert_eq!(capabilities, 4);
+        assert!(dumps[0]
+            .config
+            .extended_capabilities()
+            .any(|c| matches!(c.unwrap().kind, ExtendedCapabilityKind::Acs(_))));
+        assert!(dumps[1]
+            .config
+            .capabilities()
+            .any(|c| matches!(c.unwrap().kind, CapabilityKind::MsiX(_))));
+    }
+
+    #[test]
+    fn test_parse_lspci_verbose() {
+        let data = with_extended_capabilities();
+        let output = format!(
+            "00:14.0 USB controller: Intel Corporation Device a3ed (prog-if 30 [XHCI])\n\
+             \tSubsystem: Lenovo ThinkCentre M720q\n\
+             \tControl: I/O- Mem+ BusMaster+ SpecCycle- MemWINV- VGASnoop- ParErr- Stepping- SERR- FastB2B- DisINTx+\n\
+             \tLatency: 0\n\
+             \tInterrupt: pin A routed to IRQ 124\n\
+             \tRegion 0: Memory at f7f00000 (64-bit, non-prefetchable) [size=64K]\n\
+             \tCapabilities: [70] Power Management version 2\n\
+             \t\tFlags: PMEClk- DSI- D1- D2- AuxCurrent=375mA PME(D0-,D1-,D2-,D3hot+,D3cold+)\n\
+             \tKernel driver in use: xhci_hcd\n\
+             \tKernel modules: xhci_pci\n\
+             {}\n\
+             00:1f.0 ISA bridge: Intel Corporation Device a3c8\n\
+             \tSubsystem: Lenovo Device 3136\n\
+             \tControl: I/O+ Mem+ BusMaster+ SpecCycle- MemWINV- VGASnoop- ParErr- Stepping- SERR- FastB2B- DisINTx-\n\
+             {}",
+            hex_dump(&data),
+            hex_dump(&data[..64]),
+        );
+
+        let dumps = Dump::parse_lspci(&output).unwrap();
+        assert_eq!(dumps.len(), 2);
+        assert_eq!(dumps[0].config.as_bytes(), &data[..]);
+        assert_eq!(dumps[1].address, "00:1f.0".parse().ok());
+        assert_eq!(dumps[1].config.len(), 64);
+    }
+
+    #[test]
+    fn test_names() {
+        let ids: PciIds = "\
+8086  Intel Corporation
+\ta3ed  Comet Lake USB 3.1 xHCI Host Controller
+\t\t17aa 2292  ThinkCentre M720q
+C 0c  Serial bus controller
+\t03  USB controller
+\t\t30  XHCI
+"
+        .parse()
+        .unwrap();
+
+        let dump = Dump::from_bytes(with_extended_capabilities()).unwrap();
+        assert_eq!(dump.address, None);
+        assert_eq!(dump.vendor_name(&ids), Some("Intel Corporation"));
+        assert_eq!(
+            dump.device_name(&ids),
+            Some("Comet Lake USB 3.1 xHCI Host Controller")
+        );
+        assert_eq!(dump.subsystem_name(&ids), Some("ThinkCentre M720q"));
+        assert_eq!(dump.subclass_name(&ids), Some("USB controller"));
+        assert_eq!(dump.prog_if_name(&ids), Some("XHCI"));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let data = with_extended_capabilities();
+        let full = hex_dump(&data[..64]);
+
+        let gap: String = full
+            .lines()
+            .filter(|l| !l.starts_with("10:"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert!(matches!(
+            Dump::parse_lspci(&gap),
+            Err(Error::InvalidDump { line: 2, .. })
+        ));
+
+        let short: String = full.lines().take(2).collect::<Vec<_>>().join("\n");
+        assert!(Dump::parse_lspci(&short).is_err());
+
+        assert!(Dump::parse_lspci("00: 86 80 zz").is_err());
+        assert!(Dump::parse_lspci("garbage").is_err());
+        assert!(Dump::from_bytes(vec![0; 16]).is_err());
+    }
+}
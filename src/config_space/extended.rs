@@ -0,0 +1,610 @@
+//! This module contains the walker of the PCI Express extended capability list, which starts at offset `0x100`.
+
+use super::{ConfigSpace, CONVENTIONAL_SIZE, EXTENDED_SIZE};
+use crate::error::{Error, Result};
+use crate::ids::VendorId;
+
+/// A capability found in the extended capability list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtendedCapability {
+    /// The offset of the capability within the configuration space.
+    pub offset: u16,
+    /// The ID of the capability, e.g. `0x0001` for AER.
+    pub id: u16,
+    /// The version of the capability structure.
+    pub version: u8,
+    pub kind: ExtendedCapabilityKind,
+}
+
+/// The decoded body of an extended capability.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtendedCapabilityKind {
+    Aer(Aer),
+    DeviceSerialNumber(u64),
+    Acs(Acs),
+    Ari(Ari),
+    SrIov(SrIov),
+    ResizableBar(ResizableBar),
+    Ltr(Ltr),
+    L1PmSubstates(L1PmSubstates),
+    Dvsec(Dvsec),
+    DataLinkFeature(DataLinkFeature),
+    /// A capability which isn't decoded, its ID is held by [`ExtendedCapability::id`].
+    Other,
+}
+
+/// The advanced error reporting capability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Aer {
+    pub uncorrectable_status: u32,
+    pub uncorrectable_mask: u32,
+    pub uncorrectable_severity: u32,
+    pub correctable_status: u32,
+    pub correctable_mask: u32,
+    /// The raw advanced error capabilities and control register.
+    pub capabilities_control: u32,
+    /// The header of the transaction which caused the first error.
+    pub header_log: [u32; 4],
+}
+
+/// The access control services capability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Acs {
+    /// The services the port supports.
+    pub capabilities: AcsFlags,
+    /// The services the port enforces.
+    pub control: AcsFlags,
+}
+
+/// A set of access control services.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct AcsFlags(pub u16);
+
+/// The alternative routing-ID interpretation capability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ari {
+    pub capabilities: u16,
+    pub control: u16,
+}
+
+/// The single root I/O virtualization capability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SrIov {
+    pub capabilities: u32,
+    pub control: u16,
+    pub status: u16,
+    pub initial_vfs: u16,
+    pub total_vfs: u16,
+    pub num_vfs: u16,
+    pub function_dependency_link: u8,
+    /// The offset of the routing ID of the first virtual function from the one of the physical function.
+    pub first_vf_offset: u16,
+    /// The distance between the routing IDs of consecutive virtual functions.
+    pub vf_stride: u16,
+    pub vf_device_id: u16,
+    pub supported_page_sizes: u32,
+    pub system_page_size: u32,
+    /// The raw base address registers of the virtual functions.
+    pub bars: [u32; 6],
+}
+
+/// The resizable BAR capability.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResizableBar {
+    pub entries: Vec<ResizableBarEntry>,
+}
+
+/// A BAR listed by the resizable BAR capability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResizableBarEntry {
+    pub capabilities: u32,
+    pub control: u32,
+}
+
+/// The latency tolerance reporting capability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ltr {
+    /// The raw max snoop latency register.
+    pub max_snoop_latency: u16,
+    /// The raw max no-snoop latency register.
+    pub max_no_snoop_latency: u16,
+}
+
+/// The L1 PM substates capability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct L1PmSubstates {
+    pub capabilities: u32,
+    pub control1: u32,
+    pub control2: u32,
+}
+
+/// The designated vendor-specific extended capability, whose layout is defined by `vendor`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dvsec {
+    pub vendor: VendorId,
+    /// The ID of the capability, only meaningful along with its vendor.
+    pub id: u16,
+    pub revision: u8,
+    /// The length of the capability, in bytes.
+    pub length: u16,
+}
+
+/// The data link feature capability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataLinkFeature {
+    pub capabilities: u32,
+    pub status: u32,
+}
+
+/// An iterator over the extended capability list of a configuration space,
+/// returned by [`ConfigSpace::extended_capabilities()`].
+///
+/// A conventional PCI device has no extended capability, neither does the 256-byte configuration space
+/// of a PCI Express device behind a host bridge which can't reach the extended configuration space.
+/// The iterator stops after returning an error.
+pub struct ExtendedCapabilities<'a> {
+    config: &'a ConfigSpace,
+    next: u16,
+    // One bit per double word of the extended configuration space.
+    visited: [u64; EXTENDED_SIZE / 4 / 64],
+}
+
+impl<'a> ExtendedCapabilities<'a> {
+    pub(super) fn new(config: &'a ConfigSpace) -> Self {
+        ExtendedCapabilities {
+            config,
+            next: CONVENTIONAL_SIZE as u16,
+            visited: [0; EXTENDED_SIZE / 4 / 64],
+        }
+    }
+
+    fn read(&mut self, offset: u16) -> Result<Option<ExtendedCapability>> {
+        if usize::from(offset) < CONVENTIONAL_SIZE {
+            return Err(malformed(
+                offset,
+                "the capability lives in the conventional space",
+            ));
+        }
+        let (word, bit) = (usize::from(offset / 4 / 64), offset / 4 % 64);
+        if self.visited[word] & 1 << bit != 0 {
+            return Err(malformed(offset, "the capability list loops"));
+        }
+        self.visited[word] |= 1 << bit;
+
+        let config = self.config;
+        let offset = usize::from(offset);
+        let header = config.read_u32(offset)?;
+        // An empty list is made of a single null header, which the missing list reads as all ones.
+        if header == 0 || header == u32::MAX {
+            return Ok(None);
+        }
+        let id = header as u16;
+        self.next = (header >> 20) as u16 & 0xffc;
+
+        let kind = match id {
+            0x0001 => ExtendedCapabilityKind::Aer(Aer {
+                uncorrectable_status: config.read_u32(offset + 0x04)?,
+                uncorrectable_mask: config.read_u32(offset + 0x08)?,
+                uncorrectable_severity: config.read_u32(offset + 0x0c)?,
+                correctable_status: config.read_u32(offset + 0x10)?,
+                correctable_mask: config.read_u32(offset + 0x14)?,
+                capabilities_control: config.read_u32(offset + 0x18)?,
+                header_log: [
+                    config.read_u32(offset + 0x1c)?,
+                    config.read_u32(offset + 0x20)?,
+                    config.read_u32(offset + 0x24)?,
+                    config.read_u32(offset + 0x28)?,
+                ],
+            }),
+            0x0003 => ExtendedCapabilityKind::DeviceSerialNumber(
+                u64::from(config.read_u32(offset + 4)?)
+                    | u64::from(config.read_u32(offset + 8)?) << 32,
+            ),
+            0x000d => ExtendedCapabilityKind::Acs(Acs {
+                capabilities: AcsFlags(config.read_u16(offset + 4)?),
+                control: AcsFlags(config.read_u16(offset + 6)?),
+            }),
+            0x000e => ExtendedCapabilityKind::Ari(Ari {
+                capabilities: config.read_u16(offset + 4)?,
+                control: config.read_u16(offset + 6)?,
+            }),
+            0x0010 => {
+                let mut bars = [0; 6];
+                for (i, bar) in bars.iter_mut().enumerate() {
+                    *bar = config.read_u32(offset + 0x24 + 4 * i)?;
+                }
+                ExtendedCapabilityKind::SrIov(SrIov {
+                    capabilities: config.read_u32(offset + 0x04)?,
+                    control: config.read_u16(offset + 0x08)?,
+                    status: config.read_u16(offset + 0x0a)?,
+                    initial_vfs: config.read_u16(offset + 0x0c)?,
+                    total_vfs: config.read_u16(offset + 0x0e)?,
+                    num_vfs: config.read_u16(offset + 0x10)?,
+                    function_dependency_link: config.read_u8(offset + 0x12)?,
+                    first_vf_offset: config.read_u16(offset + 0x14)?,
+                    vf_stride: config.read_u16(offset + 0x16)?,
+                    vf_device_id: config.read_u16(offset + 0x1a)?,
+                    supported_page_sizes: config.read_u32(offset + 0x1c)?,
+                    system_page_size: config.read_u32(offset + 0x20)?,
+                    bars,
+                })
+            }
+            0x0015 => {
+                // The number of entries is held by the control register of the first one.
+                let count = config.read_u32(offset + 8)? >> 5 & 0x7;
+                let mut entries = Vec::new();
+                for i in 0..count as usize {
+                    entries.push(ResizableBarEntry {
+                        capabilities: config.read_u32(offset + 4 + 8 * i)?,
+                        control: config.read_u32(offset + 8 + 8 * i)?,
+                    });
+                }
+                ExtendedCapabilityKind::ResizableBar(ResizableBar { entries })
+            }
+            0x0018 => ExtendedCapabilityKind::Ltr(Ltr {
+                max_snoop_latency: config.read_u16(offset + 4)?,
+                max_no_snoop_latency: config.read_u16(offset + 6)?,
+            }),
+            0x001e => ExtendedCapabilityKind::L1PmSubstates(L1PmSubstates {
+                capabilities: config.read_u32(offset + 0x4)?,
+                control1: config.read_u32(offset + 0x8)?,
+                control2: config.read_u32(offset + 0xc)?,
+            }),
+            0x0023 => {
+                let header1 = config.read_u32(offset + 4)?;
+                ExtendedCapabilityKind::Dvsec(Dvsec {
+                    vendor: VendorId(header1 as u16),
+                    id: config.read_u16(offset + 8)?,
+                    revision: (header1 >> 16 & 0xf) as u8,
+                    length: (header1 >> 20) as u16,
+                })
+            }
+            0x0025 => ExtendedCapabilityKind::DataLinkFeature(DataLinkFeature {
+                capabilities: config.read_u32(offset + 4)?,
+                status: config.read_u32(offset + 8)?,
+            }),
+            _ => ExtendedCapabilityKind::Other,
+        };
+
+        Ok(Some(ExtendedCapability {
+            offset: offset as u16,
+            id,
+            version: (header >> 16 & 0xf) as u8,
+            kind,
+        }))
+    }
+}
+
+impl Iterator for ExtendedCapabilities<'_> {
+    type Item = Result<ExtendedCapability>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.next;
+        if offset == 0 {
+            return None;
+        }
+        self.next = 0;
+
+        // Only the header being available means the caller wasn't privileged,
+        // whereas a 256-byte configuration space simply has no extended capability.
+        if self.config.is_truncated() {
+            return Some(Err(Error::TruncatedConfigSpace {
+                offset: usize::from(offset),
+                len: self.config.len(),
+            }));
+        }
+        if self.config.len() < EXTENDED_SIZE {
+            return None;
+        }
+
+        match self.read(offset) {
+            Ok(Some(capability)) => Some(Ok(capability)),
+            Ok(None) => {
+                self.next = 0;
+                None
+            }
+            Err(e) => {
+                self.next = 0;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl ExtendedCapability {
+    /// This function returns the name `lspci` gives to the capability, e.g. `Access Control Services`.
+    pub fn name(&self) -> &'static str {
+        match self.id {
+            0x0001 => "Advanced Error Reporting",
+            0x0002 | 0x0009 => "Virtual Channel",
+            0x0003 => "Device Serial Number",
+            0x0004 => "Power Budgeting",
+            0x0005 => "Root Complex Link",
+            0x000b => "Vendor Specific Information",
+            0x000d => "Access Control Services",
+            0x000e => "Alternative Routing-ID Interpretation (ARI)",
+            0x000f => "Address Translation Service (ATS)",
+            0x0010 => "Single Root I/O Virtualization (SR-IOV)",
+            0x0013 => "Page Request Interface (PRI)",
+            0x0015 => "Physical Resizable BAR",
+            0x0017 => "Transaction Processing Hints",
+            0x0018 => "Latency Tolerance Reporting",
+            0x0019 => "Secondary PCI Express",
+            0x001b => "Process Address Space ID (PASID)",
+            0x001d => "Downstream Port Containment",
+            0x001e => "L1 PM Substates",
+            0x001f => "Precision Time Measurement",
+            0x0023 => "Designated Vendor-Specific",
+            0x0025 => "Data Link Feature",
+            0x0026 => "Physical Layer 16.0 GT/s",
+            0x0027 => "Lane Margining at the Receiver",
+            _ => "Unknown",
+        }
+    }
+}
+
+impl AcsFlags {
+    /// This function returns whether requests are validated against the bus numbers behind the port.
+    pub fn source_validation(&self) -> bool {
+        self.0 & 1 << 0 != 0
+    }
+
+    /// This function returns whether translated requests are blocked.
+    pub fn translation_blocking(&self) -> bool {
+        self.0 & 1 << 1 != 0
+    }
+
+    /// This function returns whether peer-to-peer requests are redirected upstream.
+    pub fn p2p_request_redirect(&self) -> bool {
+        self.0 & 1 << 2 != 0
+    }
+
+    /// This function returns whether peer-to-peer completions are redirected upstream.
+    pub fn p2p_completion_redirect(&self) -> bool {
+        self.0 & 1 << 3 != 0
+    }
+
+    /// This function returns whether upstream traffic is forwarded upstream.
+    pub fn upstream_forwarding(&self) -> bool {
+        self.0 & 1 << 4 != 0
+    }
+
+    /// This function returns whether peer-to-peer traffic is subject to egress control.
+    pub fn p2p_egress_control(&self) -> bool {
+        self.0 & 1 << 5 != 0
+    }
+
+    /// This function returns whether translated peer-to-peer requests are sent directly.
+    pub fn direct_translated_p2p(&self) -> bool {
+        self.0 & 1 << 6 != 0
+    }
+}
+
+impl Ari {
+    /// This function returns the number of the next function of the device.
+    pub fn next_function(&self) -> u8 {
+        (self.capabilities >> 8) as u8
+    }
+}
+
+impl SrIov {
+    /// This function returns whether virtual functions are enabled.
+    pub fn vf_enabled(&self) -> bool {
+        self.control & 1 != 0
+    }
+}
+
+impl ResizableBarEntry {
+    /// This function returns the index of the BAR this entry controls.
+    pub fn bar(&self) -> u8 {
+        (self.control & 0x7) as u8
+    }
+
+    /// This function returns the sizes the BAR supports, in bytes.
+    pub fn supported_sizes(&self) -> Vec<u64> {
+        // Bit 4 stands for 1 MB, bit 5 for 2 MB and so on up to bit 31.
+        (4..32)
+            .filter(|bit| self.capabilities & 1 << bit != 0)
+            .map(|bit| 1 << (20 + bit - 4))
+            .collect()
+    }
+
+    /// This function returns the current size of the BAR, in bytes, or `None` if the encoded size
+    /// doesn't fit in 64 bits, which only a malformed capability holds.
+    pub fn current_size(&self) -> Option<u64> {
+        1u64.checked_shl(20 + (self.control >> 8 & 0x3f))
+    }
+}
+
+impl Ltr {
+    /// This function returns the max snoop latency, in nanoseconds.
+    pub fn max_snoop_latency_ns(&self) -> u64 {
+        latency_ns(self.max_snoop_latency)
+    }
+
+    /// This function returns the max no-snoop latency, in nanoseconds.
+    pub fn max_no_snoop_latency_ns(&self) -> u64 {
+        latency_ns(self.max_no_snoop_latency)
+    }
+}
+
+impl L1PmSubstates {
+    /// This function returns whether the PCI-PM L1.2 substate is supported.
+    pub fn pci_pm_l1_2(&self) -> bool {
+        self.capabilities & 1 << 0 != 0
+    }
+
+    /// This function returns whether the PCI-PM L1.1 substate is supported.
+    pub fn pci_pm_l1_1(&self) -> bool {
+        self.capabilities & 1 << 1 != 0
+    }
+
+    /// This function returns whether the ASPM L1.2 substate is supported.
+    pub fn aspm_l1_2(&self) -> bool {
+        self.capabilities & 1 << 2 != 0
+    }
+
+    /// This function returns whether the ASPM L1.1 substate is supported.
+    pub fn aspm_l1_1(&self) -> bool {
+        self.capabilities & 1 << 3 != 0
+    }
+}
+
+impl DataLinkFeature {
+    /// This function returns whether the scaled flow control feature is supported.
+    pub fn scaled_flow_control(&self) -> bool {
+        self.capabilities & 1 != 0
+    }
+}
+
+/// This function decodes a latency made of a 10-bit value and a 3-bit scale, in multiples of 32 ns.
+fn latency_ns(latency: u16) -> u64 {
+    let value = u64::from(latency & 0x3ff);
+    let scale = u32::from(latency >> 10 & 0x7);
+    value << (5 * scale)
+}
+
+fn malformed(offset: u16, reason: &str) -> Error {
+    Error::MalformedCapability {
+        offset: usize::from(offset),
+        reason: reason.to_owned(),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::super::capabilities::tests::with_capabilities;
+    use super::*;
+
+    fn header(id: u16, version: u8, next: u16) -> [u8; 4] {
+        (u32::from(id) | u32::from(version) << 16 | u32::from(next) << 20).to_le_bytes()
+    }
+
+    /// This function returns the configuration space of an endpoint with extended capabilities.
+    pub(crate) fn with_extended_capabilities() -> Vec<u8> {
+        let mut data = with_capabilities();
+        data.resize(EXTENDED_SIZE, 0);
+        // AER, with a masked correctable error.
+        data[0x100..0x104].copy_from_slice(&header(0x0001, 2, 0x148));
+        data[0x114..0x118].copy_from_slice(&0x0000_2000u32.to_le_bytes());
+        // ACS, supporting source validation and enforcing it.
+        data[0x148..0x14c].copy_from_slice(&header(0x000d, 1, 0x150));
+        data[0x14c..0x150].copy_from_slice(&[0x5d, 0x00, 0x01, 0x00]);
+        // DSN.
+        data[0x150..0x154].copy_from_slice(&header(0x0003, 1, 0x160));
+        data[0x154..0x15c].copy_from_slice(&0x0011_22ff_fe33_4455u64.to_le_bytes());
+        // Resizable BAR, BAR 0 supporting 256 MB to 8 GB, currently 256 MB.
+        data[0x160..0x164].copy_from_slice(&header(0x0015, 1, 0x180));
+        data[0x164..0x168].copy_from_slice(&0x0003_f000u32.to_le_bytes());
+        data[0x168..0x16c].copy_from_slice(&0x0000_0820u32.to_le_bytes());
+        // SR-IOV, 8 of 64 virtual functions enabled.
+        data[0x180..0x184].copy_from_slice(&header(0x0010, 1, 0x1c0));
+        data[0x188] = 0x01;
+        data[0x18e..0x192].copy_from_slice(&[64, 0, 8, 0]);
+        data[0x194..0x198].copy_from_slice(&[0x80, 0x00, 0x02, 0x00]);
+        // LTR, 1 ms of max snoop latency.
+        data[0x1c0..0x1c4].copy_from_slice(&header(0x0018, 1, 0));
+        data[0x1c4..0x1c6].copy_from_slice(&(3 << 10 | 31u16).to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_extended_capabilities() {
+        let config = ConfigSpace::new(with_extended_capabilities()).unwrap();
+        let capabilities: Vec<ExtendedCapability> =
+            config.extended_capabilities().map(|c| c.unwrap()).collect();
+
+        let offsets: Vec<(u16, u16)> = capabilities.iter().map(|c| (c.offset, c.id)).collect();
+        assert_eq!(
+            offsets,
+            [
+                (0x100, 0x01),
+                (0x148, 0x0d),
+                (0x150, 0x03),
+                (0x160, 0x15),
+                (0x180, 0x10),
+                (0x1c0, 0x18)
+            ]
+        );
+        assert_eq!(capabilities[0].version, 2);
+        assert_eq!(capabilities[0].name(), "Advanced Error Reporting");
+
+        for capability in &capabilities {
+            match &capability.kind {
+                ExtendedCapabilityKind::Aer(aer) => assert_eq!(aer.correctable_mask, 0x2000),
+                ExtendedCapabilityKind::Acs(acs) => {
+                    assert!(acs.capabilities.source_validation());
+                    assert!(!acs.capabilities.translation_blocking());
+                    assert!(acs.control.source_validation());
+                    assert!(!acs.control.p2p_request_redirect());
+                }
+                ExtendedCapabilityKind::DeviceSerialNumber(serial) => {
+                    assert_eq!(*serial, 0x0011_22ff_fe33_4455)
+                }
+                ExtendedCapabilityKind::ResizableBar(rebar) => {
+                    let entry = rebar.entries[0];
+                    assert_eq!(rebar.entries.len(), 1);
+                    assert_eq!(entry.bar(), 0);
+                    assert_eq!(entry.current_size(), Some(256 << 20));
+                    let sizes = entry.supported_sizes();
+                    assert_eq!(sizes.first(), Some(&(256 << 20)));
+                    assert_eq!(sizes.last(), Some(&(8 << 30)));
+                }
+                ExtendedCapabilityKind::SrIov(sriov) => {
+                    assert!(sriov.vf_enabled());
+                    assert_eq!((sriov.total_vfs, sriov.num_vfs), (64, 8));
+                    assert_eq!((sriov.first_vf_offset, sriov.vf_stride), (0x80, 2));
+                }
+                ExtendedCapabilityKind::Ltr(ltr) => {
+                    assert_eq!(ltr.max_snoop_latency_ns(), 31 << 15)
+                }
+                kind => panic!("unexpected capability: {:?}", kind),
+            }
+        }
+    }
+
+    #[test]
+    fn test_resizable_bar_size_overflow() {
+        let entry = ResizableBarEntry {
+            capabilities: 0,
+            control: 0x3f << 8,
+        };
+        assert_eq!(entry.current_size(), None);
+
+        let entry = ResizableBarEntry {
+            capabilities: 0,
+            control: 43 << 8,
+        };
+        assert_eq!(entry.current_size(), Some(1 << 63));
+    }
+
+    #[test]
+    fn test_loop() {
+        let mut data = with_extended_capabilities();
+        data[0x1c0..0x1c4].copy_from_slice(&header(0x0018, 1, 0x148));
+        let config = ConfigSpace::new(data).unwrap();
+
+        let results: Vec<Result<ExtendedCapability>> = config.extended_capabilities().collect();
+        assert_eq!(results.len(), 7);
+        assert!(matches!(
+            results[6],
+            Err(Error::MalformedCapability { offset: 0x148, .. })
+        ));
+    }
+
+    #[test]
+    fn test_no_extended_space() {
+        let mut data = with_extended_capabilities();
+        data.truncate(CONVENTIONAL_SIZE);
+        let config = ConfigSpace::new(data.clone()).unwrap();
+        assert_eq!(config.extended_capabilities().count(), 0);
+
+        data.truncate(64);
+        let config = ConfigSpace::new(data).unwrap();
+        let results: Vec<Result<ExtendedCapability>> = config.extended_capabilities().collect();
+        assert!(matches!(
+            results[..],
+            [Err(Error::TruncatedConfigSpace { offset: 0x100, .. })]
+        ));
+    }
+}
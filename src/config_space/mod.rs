@@ -0,0 +1,641 @@
+//! This module contains [ConfigSpace], the raw configuration space of a PCI device, and its decoded header.
+//!
+//! The kernel only lets unprivileged users read the first 64 bytes of the configuration space,
+//! which hold the header. Reading past them returns [`Error::TruncatedConfigSpace`] instead of zeroes.
+
+use crate::error::{Error, Result};
+use crate::ids::{ClassCode, DeviceId, Revision, SubsystemId, VendorId};
+
+pub mod bridge;
+pub mod capabilities;
+pub mod dump;
+pub mod extended;
+
+pub use bridge::{BridgeControl, BridgeInfo, Window};
+pub use capabilities::{Capabilities, Capability, CapabilityKind, PortType};
+pub use dump::Dump;
+pub use extended::{ExtendedCapabilities, ExtendedCapability, ExtendedCapabilityKind};
+
+/// The size of the standard header, which is always readable.
+pub const HEADER_SIZE: usize = 64;
+/// The size of the configuration space of a conventional PCI device.
+pub const CONVENTIONAL_SIZE: usize = 256;
+/// The size of the configuration space of a PCI Express device.
+pub const EXTENDED_SIZE: usize = 4096;
+
+/// The configuration space of a PCI device.
+///
+/// # Examples
+///
+/// ```no_run
+/// use aparato::{Device, PCIDevice};
+/// use aparato::config_space::Header;
+///
+/// let device = PCIDevice::new("00:02.0");
+/// let config = device.config_space().unwrap();
+///
+/// if config.is_truncated() {
+///     println!("Only the header is readable, try running as root.");
+/// }
+///
+/// println!("Bus master: {}", config.command().bus_master());
+/// if let Header::Normal(header) = config.header() {
+///     println!("Interrupt pin: {:?}", header.interrupt_pin);
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigSpace {
+    data: Vec<u8>,
+}
+
+/// The coverage of a configuration space buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Extent {
+    /// Only the 64-byte header is available, which is what unprivileged users can read.
+    Header,
+    /// The 256 bytes of the conventional configuration space are available.
+    Conventional,
+    /// The 4096 bytes of the PCI Express extended configuration space are available.
+    Extended,
+}
+
+/// The command register, which controls how the device responds to and generates transactions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Command(pub u16);
+
+/// The status register, which records the events occurring on the bus.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Status(pub u16);
+
+/// The header type register, which tells the layout of the header and whether the device has several functions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct HeaderType(pub u8);
+
+/// The built-in self test register.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Bist(pub u8);
+
+/// The legacy interrupt pin used by the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InterruptPin {
+    None,
+    IntA,
+    IntB,
+    IntC,
+    IntD,
+    /// A value the specification reserves.
+    Reserved(u8),
+}
+
+/// The decoded header of a configuration space, whose layout depends on the [HeaderType].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Header {
+    /// The type 0 header of an endpoint.
+    Normal(NormalHeader),
+    /// The type 1 header of a PCI-to-PCI bridge.
+    Bridge(BridgeHeader),
+    /// A header whose layout isn't decoded, e.g. the type 2 header of a CardBus bridge.
+    Other(u8),
+}
+
+/// The type 0 header, found on endpoints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NormalHeader {
+    /// The raw base address registers.
+    pub bars: [u32; 6],
+    pub cardbus_cis_pointer: u32,
+    pub subsystem: SubsystemId,
+    pub expansion_rom_base: u32,
+    pub capabilities_pointer: u8,
+    pub interrupt_line: u8,
+    pub interrupt_pin: InterruptPin,
+    pub min_grant: u8,
+    pub max_latency: u8,
+}
+
+/// The type 1 header, found on PCI-to-PCI bridges, including PCI Express ports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BridgeHeader {
+    /// The raw base address registers.
+    pub bars: [u32; 2],
+    pub primary_bus: u8,
+    pub secondary_bus: u8,
+    pub subordinate_bus: u8,
+    pub secondary_latency_timer: u8,
+    pub io_base: u8,
+    pub io_limit: u8,
+    /// The status of the secondary bus.
+    pub secondary_status: Status,
+    pub memory_base: u16,
+    pub memory_limit: u16,
+    pub prefetchable_memory_base: u16,
+    pub prefetchable_memory_limit: u16,
+    pub prefetchable_base_upper: u32,
+    pub prefetchable_limit_upper: u32,
+    pub io_base_upper: u16,
+    pub io_limit_upper: u16,
+    pub capabilities_pointer: u8,
+    pub expansion_rom_base: u32,
+    pub interrupt_line: u8,
+    pub interrupt_pin: InterruptPin,
+    pub bridge_control: u16,
+}
+
+impl ConfigSpace {
+    /// This function returns a new `ConfigSpace` holding `data`, which must span at least the 64-byte header.
+    ///
+    /// Bytes past the 4096 bytes of the extended configuration space are discarded.
+    pub fn new(mut data: Vec<u8>) -> Result<Self> {
+        if data.len() < HEADER_SIZE {
+            return Err(Error::TruncatedConfigSpace {
+                offset: HEADER_SIZE,
+                len: data.len(),
+            });
+        }
+        data.truncate(EXTENDED_SIZE);
+        Ok(ConfigSpace { data })
+    }
+
+    /// This function returns the raw buffer.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// This function returns the number of readable bytes.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// This function always returns `false`, a configuration space holds at least the header.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// This function returns how much of the configuration space is available.
+    pub fn extent(&self) -> Extent {
+        match self.data.len() {
+            len if len >= EXTENDED_SIZE => Extent::Extended,
+            len if len >= CONVENTIONAL_SIZE => Extent::Conventional,
+            _ => Extent::Header,
+        }
+    }
+
+    /// This function returns whether only the header is available, which usually means the configuration
+    /// space was read by an unprivileged user.
+    pub fn is_truncated(&self) -> bool {
+        self.extent() == Extent::Header
+    }
+
+    /// This function returns the byte located at `offset`.
+    pub fn read_u8(&self, offset: usize) -> Result<u8> {
+        Ok(self.read::<1>(offset)?[0])
+    }
+
+    /// This function returns the little-endian word located at `offset`.
+    pub fn read_u16(&self, offset: usize) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.read(offset)?))
+    }
+
+    /// This function returns the little-endian double word located at `offset`.
+    pub fn read_u32(&self, offset: usize) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.read(offset)?))
+    }
+
+    /// This function returns the vendor ID.
+    pub fn vendor_id(&self) -> VendorId {
+        VendorId(self.header_u16(0x00))
+    }
+
+    /// This function returns the device ID.
+    pub fn device_id(&self) -> DeviceId {
+        DeviceId(self.header_u16(0x02))
+    }
+
+    /// This function returns the command register.
+    pub fn command(&self) -> Command {
+        Command(self.header_u16(0x04))
+    }
+
+    /// This function returns the status register.
+    pub fn status(&self) -> Status {
+        Status(self.header_u16(0x06))
+    }
+
+    /// This function returns the revision.
+    pub fn revision(&self) -> Revision {
+        Revision(self.data[0x08])
+    }
+
+    /// This function returns the class code.
+    pub fn class_code(&self) -> ClassCode {
+        ClassCode::new(self.data[0x0b], self.data[0x0a], self.data[0x09])
+    }
+
+    /// This function returns the cache line size, in double words.
+    pub fn cache_line_size(&self) -> u8 {
+        self.data[0x0c]
+    }
+
+    /// This function returns the latency timer, in bus clocks.
+    pub fn latency_timer(&self) -> u8 {
+        self.data[0x0d]
+    }
+
+    /// This function returns the header type register.
+    pub fn header_type(&self) -> HeaderType {
+        HeaderType(self.data[0x0e])
+    }
+
+    /// This function returns the built-in self test register.
+    pub fn bist(&self) -> Bist {
+        Bist(self.data[0x0f])
+    }
+
+    /// This function returns the subsystem of the device.
+    ///
+    /// The subsystem of an endpoint lives in its header, bridges store it in a capability instead.
+    pub fn subsystem(&self) -> Option<SubsystemId> {
+        match self.header() {
+            Header::Normal(header) => Some(header.subsystem),
+            _ => self.capabilities().flatten().find_map(|c| match c.kind {
+                CapabilityKind::BridgeSubsystem(subsystem) => Some(subsystem),
+                _ => None,
+            }),
+        }
+    }
+
+    /// This function returns the header, decoded according to its type.
+    pub fn header(&self) -> Header {
+        match self.header_type().layout() {
+            0 => Header::Normal(NormalHeader {
+                bars: [
+                    self.header_u32(0x10),
+                    self.header_u32(0x14),
+                    self.header_u32(0x18),
+                    self.header_u32(0x1c),
+                    self.header_u32(0x20),
+                    self.header_u32(0x24),
+                ],
+                cardbus_cis_pointer: self.header_u32(0x28),
+                subsystem: SubsystemId::new(
+                    VendorId(self.header_u16(0x2c)),
+                    DeviceId(self.header_u16(0x2e)),
+                ),
+                expansion_rom_base: self.header_u32(0x30),
+                capabilities_pointer: self.data[0x34],
+                interrupt_line: self.data[0x3c],
+                interrupt_pin: InterruptPin::from(self.data[0x3d]),
+                min_grant: self.data[0x3e],
+                max_latency: self.data[0x3f],
+            }),
+            1 => Header::Bridge(BridgeHeader {
+                bars: [self.header_u32(0x10), self.header_u32(0x14)],
+                primary_bus: self.data[0x18],
+                secondary_bus: self.data[0x19],
+                subordinate_bus: self.data[0x1a],
+                secondary_latency_timer: self.data[0x1b],
+                io_base: self.data[0x1c],
+                io_limit: self.data[0x1d],
+                secondary_status: Status(self.header_u16(0x1e)),
+                memory_base: self.header_u16(0x20),
+                memory_limit: self.header_u16(0x22),
+                prefetchable_memory_base: self.header_u16(0x24),
+                prefetchable_memory_limit: self.header_u16(0x26),
+                prefetchable_base_upper: self.header_u32(0x28),
+                prefetchable_limit_upper: self.header_u32(0x2c),
+                io_base_upper: self.header_u16(0x30),
+                io_limit_upper: self.header_u16(0x32),
+                capabilities_pointer: self.data[0x34],
+                expansion_rom_base: self.header_u32(0x38),
+                interrupt_line: self.data[0x3c],
+                interrupt_pin: InterruptPin::from(self.data[0x3d]),
+                bridge_control: self.header_u16(0x3e),
+            }),
+            layout => Header::Other(layout),
+        }
+    }
+
+    /// This function returns an iterator over the standard capability list.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use aparato::{Device, PCIDevice};
+    ///
+    /// let config = PCIDevice::new("00:02.0").config_space().unwrap();
+    /// for capability in config.capabilities() {
+    ///     match capability {
+    ///         Ok(c) => println!("[{:02x}] {}", c.offset, c.name()),
+    ///         Err(e) => eprintln!("{}", e),
+    ///     }
+    /// }
+    /// ```
+    pub fn capabilities(&self) -> Capabilities<'_> {
+        Capabilities::new(self)
+    }
+
+    /// This function returns an iterator over the PCI Express extended capability list.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use aparato::config_space::ExtendedCapabilityKind;
+    /// use aparato::PCIDevice;
+    ///
+    /// // The devices whose access control services validate the source of requests.
+    /// let validating: Vec<PCIDevice> = PCIDevice::iter()
+    ///     .filter(|device| match device.config_space() {
+    ///         Ok(config) => config.extended_capabilities().flatten().any(|c| match c.kind {
+    ///             ExtendedCapabilityKind::Acs(acs) => acs.control.source_validation(),
+    ///             _ => false,
+    ///         }),
+    ///         Err(_) => false,
+    ///     })
+    ///     .collect();
+    /// ```
+    pub fn extended_capabilities(&self) -> ExtendedCapabilities<'_> {
+        ExtendedCapabilities::new(self)
+    }
+
+    fn read<const N: usize>(&self, offset: usize) -> Result<[u8; N]> {
+        let bytes = offset
+            .checked_add(N)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or(Error::TruncatedConfigSpace {
+                offset,
+                len: self.data.len(),
+            })?;
+
+        let mut value = [0; N];
+        value.copy_from_slice(bytes);
+        Ok(value)
+    }
+
+    // The header is always available, reading it can't fail.
+    fn header_u16(&self, offset: usize) -> u16 {
+        u16::from_le_bytes([self.data[offset], self.data[offset + 1]])
+    }
+
+    fn header_u32(&self, offset: usize) -> u32 {
+        u32::from_le_bytes([
+            self.data[offset],
+            self.data[offset + 1],
+            self.data[offset + 2],
+            self.data[offset + 3],
+        ])
+    }
+}
+
+impl Command {
+    /// This function returns whether the device responds to I/O space accesses.
+    pub fn io_space(&self) -> bool {
+        self.0 & 1 << 0 != 0
+    }
+
+    /// This function returns whether the device responds to memory space accesses.
+    pub fn memory_space(&self) -> bool {
+        self.0 & 1 << 1 != 0
+    }
+
+    /// This function returns whether the device can initiate transactions, including DMA.
+    pub fn bus_master(&self) -> bool {
+        self.0 & 1 << 2 != 0
+    }
+
+    /// This function returns whether the device monitors special cycles.
+    pub fn special_cycles(&self) -> bool {
+        self.0 & 1 << 3 != 0
+    }
+
+    /// This function returns whether the device can generate memory write and invalidate commands.
+    pub fn memory_write_invalidate(&self) -> bool {
+        self.0 & 1 << 4 != 0
+    }
+
+    /// This function returns whether the device snoops VGA palette writes.
+    pub fn vga_palette_snoop(&self) -> bool {
+        self.0 & 1 << 5 != 0
+    }
+
+    /// This function returns whether the device reacts to parity errors.
+    pub fn parity_error_response(&self) -> bool {
+        self.0 & 1 << 6 != 0
+    }
+
+    /// This function returns whether the device reports system errors.
+    pub fn serr(&self) -> bool {
+        self.0 & 1 << 8 != 0
+    }
+
+    /// This function returns whether the device can generate fast back-to-back transactions.
+    pub fn fast_back_to_back(&self) -> bool {
+        self.0 & 1 << 9 != 0
+    }
+
+    /// This function returns whether the device is prevented from asserting its legacy interrupt.
+    pub fn interrupt_disable(&self) -> bool {
+        self.0 & 1 << 10 != 0
+    }
+}
+
+impl Status {
+    /// This function returns whether the device is asserting its legacy interrupt.
+    pub fn interrupt_status(&self) -> bool {
+        self.0 & 1 << 3 != 0
+    }
+
+    /// This function returns whether the device implements a capabilities list.
+    pub fn capabilities_list(&self) -> bool {
+        self.0 & 1 << 4 != 0
+    }
+
+    /// This function returns whether the device can run at 66 MHz.
+    pub fn mhz66(&self) -> bool {
+        self.0 & 1 << 5 != 0
+    }
+
+    /// This function returns whether the device accepts fast back-to-back transactions.
+    pub fn fast_back_to_back(&self) -> bool {
+        self.0 & 1 << 7 != 0
+    }
+
+    /// This function returns whether the device detected a parity error while mastering the bus.
+    pub fn master_data_parity_error(&self) -> bool {
+        self.0 & 1 << 8 != 0
+    }
+
+    /// This function returns the DEVSEL timing, 0 being fast, 1 medium and 2 slow.
+    pub fn devsel_timing(&self) -> u8 {
+        (self.0 >> 9 & 0b11) as u8
+    }
+
+    /// This function returns whether the device terminated a transaction with a target abort.
+    pub fn signaled_target_abort(&self) -> bool {
+        self.0 & 1 << 11 != 0
+    }
+
+    /// This function returns whether a transaction of the device was terminated with a target abort.
+    pub fn received_target_abort(&self) -> bool {
+        self.0 & 1 << 12 != 0
+    }
+
+    /// This function returns whether a transaction of the device was terminated with a master abort.
+    pub fn received_master_abort(&self) -> bool {
+        self.0 & 1 << 13 != 0
+    }
+
+    /// This function returns whether the device reported a system error.
+    pub fn signaled_system_error(&self) -> bool {
+        self.0 & 1 << 14 != 0
+    }
+
+    /// This function returns whether the device detected a parity error.
+    pub fn detected_parity_error(&self) -> bool {
+        self.0 & 1 << 15 != 0
+    }
+}
+
+impl HeaderType {
+    /// This function returns the layout of the header, 0 for endpoints, 1 for bridges and 2 for CardBus bridges.
+    pub fn layout(&self) -> u8 {
+        self.0 & 0x7f
+    }
+
+    /// This function returns whether the device has several functions.
+    pub fn multifunction(&self) -> bool {
+        self.0 & 0x80 != 0
+    }
+}
+
+impl Bist {
+    /// This function returns whether the device supports the built-in self test.
+    pub fn capable(&self) -> bool {
+        self.0 & 0x80 != 0
+    }
+
+    /// This function returns whether the self test is running.
+    pub fn running(&self) -> bool {
+        self.0 & 0x40 != 0
+    }
+
+    /// This function returns the completion code of the last self test, 0 meaning success.
+    pub fn completion_code(&self) -> u8 {
+        self.0 & 0x0f
+    }
+}
+
+impl From<u8> for InterruptPin {
+    fn from(pin: u8) -> Self {
+        match pin {
+            0 => InterruptPin::None,
+            1 => InterruptPin::IntA,
+            2 => InterruptPin::IntB,
+            3 => InterruptPin::IntC,
+            4 => InterruptPin::IntD,
+            pin => InterruptPin::Reserved(pin),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// This function returns the configuration space of an Intel xHCI controller, as read by root.
+    pub(crate) fn endpoint() -> Vec<u8> {
+        let mut data = vec![0; CONVENTIONAL_SIZE];
+        data[..0x10].copy_from_slice(&[
+            0x86, 0x80, 0xed, 0xa3, 0x06, 0x04, 0x90, 0x02, 0x10, 0x30, 0x03, 0x0c, 0x00, 0x00,
+            0x00, 0x00,
+        ]);
+        data[0x10..0x14].copy_from_slice(&0xf7f0_0004u32.to_le_bytes());
+        data[0x2c..0x30].copy_from_slice(&[0xaa, 0x17, 0x92, 0x22]);
+        data[0x34] = 0x70;
+        data[0x3c] = 0xff;
+        data[0x3d] = 0x01;
+        data
+    }
+
+    /// This function returns the configuration space of a PCI Express root port, as read by root.
+    pub(crate) fn bridge() -> Vec<u8> {
+        let mut data = vec![0; CONVENTIONAL_SIZE];
+        data[..0x10].copy_from_slice(&[
+            0x86, 0x80, 0x10, 0xa3, 0x07, 0x04, 0x10, 0x00, 0xf0, 0x00, 0x04, 0x06, 0x00, 0x00,
+            0x81, 0x00,
+        ]);
+        data[0x18..0x1c].copy_from_slice(&[0x00, 0x02, 0x03, 0x00]);
+        data[0x1c..0x1e].copy_from_slice(&[0xf0, 0x00]);
+        data[0x20..0x24].copy_from_slice(&[0x00, 0xf7, 0x00, 0xf7]);
+        data[0x34] = 0x40;
+        data[0x3d] = 0x01;
+        data[0x3e..0x40].copy_from_slice(&0x0010u16.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_endpoint() {
+        let config = ConfigSpace::new(endpoint()).unwrap();
+        assert_eq!(config.extent(), Extent::Conventional);
+        assert_eq!(config.vendor_id(), VendorId(0x8086));
+        assert_eq!(config.device_id(), DeviceId(0xa3ed));
+        assert_eq!(config.class_code(), ClassCode::new(0x0c, 0x03, 0x30));
+        assert_eq!(config.revision(), Revision(0x10));
+
+        let command = config.command();
+        assert!(command.memory_space() && command.bus_master() && command.interrupt_disable());
+        assert!(!command.io_space());
+        assert!(config.status().capabilities_list());
+        assert_eq!(config.status().devsel_timing(), 1);
+        assert!(!config.header_type().multifunction());
+
+        match config.header() {
+            Header::Normal(header) => {
+                assert_eq!(header.bars[0], 0xf7f0_0004);
+                assert_eq!(header.subsystem.to_string(), "17aa:2292");
+                assert_eq!(header.capabilities_pointer, 0x70);
+                assert_eq!(header.interrupt_pin, InterruptPin::IntA);
+            }
+            header => panic!("unexpected header: {:?}", header),
+        }
+    }
+
+    #[test]
+    fn test_bridge() {
+        let config = ConfigSpace::new(bridge()).unwrap();
+        assert!(config.header_type().multifunction());
+
+        match config.header() {
+            Header::Bridge(header) => {
+                assert_eq!(header.primary_bus, 0x00);
+                assert_eq!(header.secondary_bus, 0x02);
+                assert_eq!(header.subordinate_bus, 0x03);
+                assert_eq!(header.memory_base, 0xf700);
+                assert_eq!(header.bridge_control, 0x0010);
+            }
+            header => panic!("unexpected header: {:?}", header),
+        }
+    }
+
+    #[test]
+    fn test_truncated() {
+        let mut data = endpoint();
+        data.truncate(HEADER_SIZE);
+        let config = ConfigSpace::new(data).unwrap();
+
+        assert!(config.is_truncated());
+        assert_eq!(config.read_u8(0x3d).unwrap(), 0x01);
+        match config.read_u16(0x70) {
+            Err(Error::TruncatedConfigSpace { offset, len }) => {
+                assert_eq!((offset, len), (0x70, 64))
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+        assert!(config.read_u32(0x3e).is_err());
+        assert!(matches!(
+            config.read_u32(usize::MAX - 1),
+            Err(Error::TruncatedConfigSpace { .. })
+        ));
+
+        assert!(ConfigSpace::new(vec![0; 16]).is_err());
+    }
+}
@@ -0,0 +1,291 @@
+//! This module contains [Context], which tells aparato where to look for PCI devices and the pci.ids database.
+
+use crate::error::{Error, Result};
+use crate::modalias::ModuleAliases;
+use crate::pci_ids::PciIds;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+/// This is where sysfs is usually mounted.
+const DEFAULT_SYSFS_ROOT: &str = "/sys";
+/// This environment variable can be used to point aparato at a pci.ids file.
+pub const PCI_IDS_ENV: &str = "APARATO_PCI_IDS";
+/// These are the places where distributions usually install the pci.ids file, in order of preference.
+pub const PCI_IDS_SEARCH_PATHS: &[&str] = &[
+    "/usr/share/hwdata/pci.ids",
+    "/usr/share/misc/pci.ids",
+    "/usr/share/pci.ids",
+];
+/// This file holds the release of the running kernel, which the modules are installed under.
+const OSRELEASE_PATH: &str = "/proc/sys/kernel/osrelease";
+
+/// A `Context` holds the location of sysfs and the pci.ids database.
+///
+/// The pci.ids database is only read the first time it's needed, and is shared between clones of the same `Context`.
+///
+/// # Examples
+///
+/// ```
+/// use aparato::{Context, Device, PCIDevice};
+///
+/// // The host's sysfs is mounted at /host/sys inside of the container.
+/// let context = Context::builder()
+///     .sysfs_root("/host/sys")
+///     .pci_ids_path("/usr/share/misc/pci.ids")
+///     .build();
+///
+/// let device = PCIDevice::with_context("00:02.0", &context);
+/// ```
+#[derive(Clone)]
+pub struct Context {
+    sysfs_root: PathBuf,
+    pci_ids_path: Option<PathBuf>,
+    pci_ids: Arc<OnceLock<Result<PciIds>>>,
+    modules_alias_path: Arc<OnceLock<Option<PathBuf>>>,
+    module_aliases: Arc<OnceLock<Result<ModuleAliases>>>,
+    dry_run: bool,
+}
+
+/// A builder for [Context].
+#[derive(Debug, Default)]
+pub struct ContextBuilder {
+    sysfs_root: Option<PathBuf>,
+    pci_ids_path: Option<PathBuf>,
+    pci_ids: Option<PciIds>,
+    modules_alias_path: Option<PathBuf>,
+    module_aliases: Option<ModuleAliases>,
+    dry_run: bool,
+}
+
+impl Context {
+    /// This function returns a `Context` with the default settings, see [`ContextBuilder`].
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    /// This function returns a [`ContextBuilder`].
+    pub fn builder() -> ContextBuilder {
+        ContextBuilder::default()
+    }
+
+    /// This function returns the `Context` used by the functions which don't take one.
+    pub fn global() -> &'static Context {
+        static GLOBAL: OnceLock<Context> = OnceLock::new();
+        GLOBAL.get_or_init(Context::new)
+    }
+
+    /// This function returns the path at which sysfs is mounted.
+    pub fn sysfs_root(&self) -> &Path {
+        &self.sysfs_root
+    }
+
+    /// This function returns the directory holding the PCI devices.
+    pub fn pci_devices_path(&self) -> PathBuf {
+        self.sysfs_root.join("bus/pci/devices")
+    }
+
+    /// This function returns the path of the pci.ids database, if one was found.
+    pub fn pci_ids_path(&self) -> Option<&Path> {
+        self.pci_ids_path.as_deref()
+    }
+
+    /// This function returns the pci.ids database, which is read and parsed the first time it's needed.
+    pub fn pci_ids(&self) -> Result<&PciIds> {
+        self.pci_ids
+            .get_or_init(|| match &self.pci_ids_path {
+                Some(path) => PciIds::open(path),
+                None => Err(Error::PciIdsMissing(search_paths())),
+            })
+            .as_ref()
+            .map_err(Error::clone)
+    }
+
+    /// This function returns the path of the modules.alias file, if the release of the running kernel could be found.
+    ///
+    /// The release is only read the first time it's needed.
+    pub fn modules_alias_path(&self) -> Option<&Path> {
+        self.modules_alias_path
+            .get_or_init(|| {
+                let release = std::fs::read_to_string(OSRELEASE_PATH).ok()?;
+                Some(
+                    Path::new("/lib/modules")
+                        .join(release.trim())
+                        .join("modules.alias"),
+                )
+            })
+            .as_deref()
+    }
+
+    /// This function returns the aliases of the kernel modules, which are read and parsed the first time they're needed.
+    pub fn module_aliases(&self) -> Result<&ModuleAliases> {
+        self.module_aliases
+            .get_or_init(|| match self.modules_alias_path() {
+                Some(path) => ModuleAliases::open(path),
+                None => Err(Error::io(
+                    OSRELEASE_PATH,
+                    io::Error::from(io::ErrorKind::NotFound),
+                )),
+            })
+            .as_ref()
+            .map_err(Error::clone)
+    }
+
+    /// This function returns whether the functions writing to sysfs only report the writes they would perform.
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Context {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Context")
+            .field("sysfs_root", &self.sysfs_root)
+            .field("pci_ids_path", &self.pci_ids_path)
+            .field("modules_alias_path", &self.modules_alias_path.get())
+            .field("dry_run", &self.dry_run)
+            .finish()
+    }
+}
+
+impl ContextBuilder {
+    /// This function sets the path at which sysfs is mounted, `/sys` by default.
+    pub fn sysfs_root<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.sysfs_root = Some(path.into());
+        self
+    }
+
+    /// This function sets the path of the pci.ids database.
+    ///
+    /// By default, the path held by the `APARATO_PCI_IDS` environment variable is used,
+    /// and if it isn't set, the first of [`PCI_IDS_SEARCH_PATHS`] which exists.
+    pub fn pci_ids_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.pci_ids_path = Some(path.into());
+        self
+    }
+
+    /// This function sets an already parsed pci.ids database, which takes precedence over [`ContextBuilder::pci_ids_path()`].
+    pub fn pci_ids(mut self, ids: PciIds) -> Self {
+        self.pci_ids = Some(ids);
+        self
+    }
+
+    /// This function sets the path of the modules.alias file,
+    /// `/lib/modules/$(uname -r)/modules.alias` by default.
+    pub fn modules_alias_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.modules_alias_path = Some(path.into());
+        self
+    }
+
+    /// This function sets already parsed module aliases, which take precedence over [`ContextBuilder::modules_alias_path()`].
+    pub fn module_aliases(mut self, aliases: ModuleAliases) -> Self {
+        self.module_aliases = Some(aliases);
+        self
+    }
+
+    /// This function sets whether the functions writing to sysfs, such as [`unbind()`](crate::linux::LinuxPCIDevice::unbind),
+    /// leave sysfs untouched and only report the writes they would perform, `false` by default.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// This function returns the [`Context`] described by the builder.
+    pub fn build(self) -> Context {
+        let pci_ids_path = self.pci_ids_path.or_else(|| {
+            let candidates = search_paths();
+            candidates.iter().find(|p| p.is_file()).cloned()
+        });
+
+        let pci_ids = OnceLock::new();
+        if let Some(ids) = self.pci_ids {
+            let _ = pci_ids.set(Ok(ids));
+        }
+
+        let modules_alias_path = OnceLock::new();
+        if let Some(path) = self.modules_alias_path {
+            let _ = modules_alias_path.set(Some(path));
+        }
+
+        let module_aliases = OnceLock::new();
+        if let Some(aliases) = self.module_aliases {
+            let _ = module_aliases.set(Ok(aliases));
+        }
+
+        Context {
+            sysfs_root: self
+                .sysfs_root
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_SYSFS_ROOT)),
+            pci_ids_path,
+            pci_ids: Arc::new(pci_ids),
+            modules_alias_path: Arc::new(modules_alias_path),
+            module_aliases: Arc::new(module_aliases),
+            dry_run: self.dry_run,
+        }
+    }
+}
+
+/// This function returns the paths at which the pci.ids database is looked for.
+fn search_paths() -> Vec<PathBuf> {
+    std::env::var_os(PCI_IDS_ENV)
+        .map(|path| vec![PathBuf::from(path)])
+        .unwrap_or_else(|| PCI_IDS_SEARCH_PATHS.iter().map(PathBuf::from).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sysfs_root() {
+        let context = Context::builder().sysfs_root("/host/sys").build();
+        assert_eq!(context.sysfs_root(), Path::new("/host/sys"));
+        assert_eq!(
+            context.pci_devices_path(),
+            PathBuf::from("/host/sys/bus/pci/devices")
+        );
+    }
+
+    #[test]
+    fn test_missing_pci_ids() {
+        let context = Context::builder()
+            .pci_ids_path("/nonexistent/pci.ids")
+            .build();
+        assert!(matches!(context.pci_ids(), Err(Error::PciIdsMissing(_))));
+    }
+
+    #[test]
+    fn test_lazy_modules_alias_path() {
+        // The release of the kernel isn't read until the path is needed.
+        let context = Context::new();
+        assert!(context.modules_alias_path.get().is_none());
+        context.modules_alias_path();
+        assert!(context.modules_alias_path.get().is_some());
+
+        let context = Context::builder()
+            .modules_alias_path("/tmp/modules.alias")
+            .build();
+        assert_eq!(
+            context.modules_alias_path(),
+            Some(Path::new("/tmp/modules.alias"))
+        );
+    }
+
+    #[test]
+    fn test_shared_pci_ids() {
+        let ids: PciIds = "8086  Intel Corporation\n".parse().unwrap();
+        let context = Context::builder().pci_ids(ids).build();
+        let clone = context.clone();
+        assert_eq!(
+            clone.pci_ids().unwrap().vendor_name(0x8086),
+            Some("Intel Corporation")
+        );
+    }
+}
@@ -3,6 +3,7 @@
 use std::fmt;
 
 /// This enum holds variants that are defined as classes in <https://pci-ids.ucw.cz/read/PD/>.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DeviceClass {
     Unclassified,                      // ID: 00
     MassStorageController,             // ID: 01
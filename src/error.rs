@@ -0,0 +1,168 @@
+//! This module contains [Error], the error type returned by the fallible functions of aparato.
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// A specialized [`Result`](std::result::Result) type for aparato's fallible functions.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// This enum holds the different reasons for which querying a PCI device can fail.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The PCI device does not exist, the path of the device is held by this variant.
+    NotFound(PathBuf),
+    /// The string isn't a valid PCI address.
+    InvalidAddress(String),
+    /// The string isn't a valid hexadecimal identifier.
+    InvalidId(String),
+    /// The string isn't a valid PCI Express link speed.
+    InvalidLinkSpeed(String),
+    /// An I/O error occurred while accessing the given path.
+    Io { path: PathBuf, source: io::Error },
+    /// An attribute of the PCI device holds a value that couldn't be parsed.
+    MalformedAttribute { path: PathBuf, value: String },
+    /// The pci.ids database couldn't be found at any of the given paths.
+    PciIdsMissing(Vec<PathBuf>),
+    /// The pci.ids database contains an entry that couldn't be parsed, reported through [`PciIds::warnings()`](crate::pci_ids::PciIds::warnings),
+    /// or a line of it couldn't be read.
+    InvalidPciIds { line: usize, reason: String },
+    /// The configuration space ends before `offset`, only its first `len` bytes are available.
+    ///
+    /// Unprivileged users can only read the first 64 bytes of the configuration space.
+    TruncatedConfigSpace { offset: usize, len: usize },
+    /// The capability located at `offset` is malformed, e.g. the capability list loops back to it.
+    MalformedCapability { offset: usize, reason: String },
+    /// A configuration space dump contains a line that couldn't be parsed.
+    InvalidDump { line: usize, reason: String },
+    /// The string isn't a valid PCI modalias.
+    InvalidModalias(String),
+    /// More virtual functions were requested from the device located at `path` than the `total` it supports.
+    TooManyVfs {
+        path: PathBuf,
+        requested: u16,
+        total: u16,
+    },
+    /// Writing to the given sysfs file was denied, which usually means root privileges are required.
+    PermissionDenied(PathBuf),
+}
+
+impl Error {
+    pub(crate) fn io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Error::Io {
+            path: path.into(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NotFound(path) => write!(f, "no PCI device found at {}", path.display()),
+            Error::InvalidAddress(address) => write!(f, "invalid PCI address: {:?}", address),
+            Error::InvalidId(id) => write!(f, "invalid identifier: {:?}", id),
+            Error::InvalidLinkSpeed(speed) => write!(f, "invalid link speed: {:?}", speed),
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::MalformedAttribute { path, value } => {
+                write!(f, "{} holds a malformed value: {:?}", path.display(), value)
+            }
+            Error::PciIdsMissing(paths) => {
+                let paths: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+                write!(f, "pci.ids database not found in {}", paths.join(", "))
+            }
+            Error::InvalidPciIds { line, reason } => {
+                write!(f, "invalid pci.ids database on line {}: {}", line, reason)
+            }
+            Error::TruncatedConfigSpace { offset, len } => write!(
+                f,
+                "offset {:#x} is past the {} readable bytes of the configuration space",
+                offset, len
+            ),
+            Error::MalformedCapability { offset, reason } => {
+                write!(f, "malformed capability at {:#x}: {}", offset, reason)
+            }
+            Error::InvalidDump { line, reason } => {
+                write!(
+                    f,
+                    "invalid configuration space dump on line {}: {}",
+                    line, reason
+                )
+            }
+            Error::InvalidModalias(modalias) => write!(f, "invalid PCI modalias: {:?}", modalias),
+            Error::TooManyVfs {
+                path,
+                requested,
+                total,
+            } => write!(
+                f,
+                "{} virtual functions requested from {}, which supports at most {}",
+                requested,
+                path.display(),
+                total
+            ),
+            Error::PermissionDenied(path) => {
+                write!(f, "permission denied writing to {}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+// `io::Error` can't be cloned, it is rebuilt from its kind and message instead.
+// This allows failures, such as a missing pci.ids database, to be cached and reported more than once.
+impl Clone for Error {
+    fn clone(&self) -> Self {
+        match self {
+            Error::NotFound(path) => Error::NotFound(path.clone()),
+            Error::InvalidAddress(address) => Error::InvalidAddress(address.clone()),
+            Error::InvalidId(id) => Error::InvalidId(id.clone()),
+            Error::InvalidLinkSpeed(speed) => Error::InvalidLinkSpeed(speed.clone()),
+            Error::Io { path, source } => Error::io(
+                path.clone(),
+                io::Error::new(source.kind(), source.to_string()),
+            ),
+            Error::MalformedAttribute { path, value } => Error::MalformedAttribute {
+                path: path.clone(),
+                value: value.clone(),
+            },
+            Error::PciIdsMissing(paths) => Error::PciIdsMissing(paths.clone()),
+            Error::InvalidPciIds { line, reason } => Error::InvalidPciIds {
+                line: *line,
+                reason: reason.clone(),
+            },
+            Error::TruncatedConfigSpace { offset, len } => Error::TruncatedConfigSpace {
+                offset: *offset,
+                len: *len,
+            },
+            Error::MalformedCapability { offset, reason } => Error::MalformedCapability {
+                offset: *offset,
+                reason: reason.clone(),
+            },
+            Error::InvalidDump { line, reason } => Error::InvalidDump {
+                line: *line,
+                reason: reason.clone(),
+            },
+            Error::InvalidModalias(modalias) => Error::InvalidModalias(modalias.clone()),
+            Error::TooManyVfs {
+                path,
+                requested,
+                total,
+            } => Error::TooManyVfs {
+                path: path.clone(),
+                requested: *requested,
+                total: *total,
+            },
+            Error::PermissionDenied(path) => Error::PermissionDenied(path.clone()),
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! This module contains the typed identifiers of a PCI device.
+//!
+//! Every identifier is displayed and parsed as hexadecimal, the same way `lspci -n` shows them.
+//! The `0x` prefix used by sysfs is accepted when parsing.
+
+use crate::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// The ID of the vendor of a PCI device, e.g. `8086` for Intel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VendorId(pub u16);
+
+/// The ID of a PCI device, which is only meaningful along with its [VendorId].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DeviceId(pub u16);
+
+/// The revision of a PCI device.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Revision(pub u8);
+
+/// The class code of a PCI device, e.g. `0c0330` for a USB xHCI controller.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ClassCode {
+    pub class: u8,
+    pub subclass: u8,
+    /// The programming interface.
+    pub prog_if: u8,
+}
+
+/// The subsystem of a PCI device, which usually identifies the board the device was built into.
+///
+/// It is displayed as `vendor:device`, e.g. `17aa:2292`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SubsystemId {
+    pub vendor: VendorId,
+    pub device: DeviceId,
+}
+
+impl ClassCode {
+    /// This function returns a new `ClassCode`.
+    pub fn new(class: u8, subclass: u8, prog_if: u8) -> Self {
+        ClassCode {
+            class,
+            subclass,
+            prog_if,
+        }
+    }
+}
+
+impl SubsystemId {
+    /// This function returns a new `SubsystemId`.
+    pub fn new(vendor: VendorId, device: DeviceId) -> Self {
+        SubsystemId { vendor, device }
+    }
+}
+
+impl fmt::Display for VendorId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04x}", self.0)
+    }
+}
+
+impl fmt::Display for DeviceId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04x}", self.0)
+    }
+}
+
+impl fmt::Display for Revision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02x}", self.0)
+    }
+}
+
+impl fmt::Display for ClassCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:02x}{:02x}{:02x}",
+            self.class, self.subclass, self.prog_if
+        )
+    }
+}
+
+impl fmt::Display for SubsystemId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.vendor, self.device)
+    }
+}
+
+impl FromStr for VendorId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(VendorId(parse_hex(s, &[4])? as u16))
+    }
+}
+
+impl FromStr for DeviceId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(DeviceId(parse_hex(s, &[4])? as u16))
+    }
+}
+
+impl FromStr for Revision {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Revision(parse_hex(s, &[2])? as u8))
+    }
+}
+
+impl FromStr for ClassCode {
+    type Err = Error;
+
+    /// This function accepts either a full class code, e.g. `0c0330`,
+    /// or a class and subclass, e.g. `0c03`, in which case the programming interface is 0.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = hex_digits(s).len();
+        let mut value = parse_hex(s, &[4, 6])?;
+        if digits == 4 {
+            value <<= 8;
+        }
+
+        let [_, class, subclass, prog_if] = value.to_be_bytes();
+        Ok(ClassCode::new(class, subclass, prog_if))
+    }
+}
+
+impl FromStr for SubsystemId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (vendor, device) = s
+            .split_once(':')
+            .ok_or_else(|| Error::InvalidId(s.to_owned()))?;
+        Ok(SubsystemId::new(vendor.parse()?, device.parse()?))
+    }
+}
+
+/// This function returns the digits of a hexadecimal ID, stripped of a single `0x` or `0X` prefix.
+fn hex_digits(s: &str) -> &str {
+    let s = s.trim();
+    s.strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s)
+}
+
+/// This function parses a hexadecimal ID, optionally prefixed with `0x`, whose length is one of `widths`.
+fn parse_hex(s: &str, widths: &[usize]) -> Result<u32, Error> {
+    let digits = hex_digits(s);
+    if !widths.contains(&digits.len()) || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(Error::InvalidId(s.to_owned()));
+    }
+    u32::from_str_radix(digits, 16).map_err(|_| Error::InvalidId(s.to_owned()))
+}
+
+#[cfg(feature = "serde")]
+crate::serde_string::impl_serde_as_string!(VendorId, DeviceId, Revision, ClassCode, SubsystemId);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let vendor: VendorId = "0x8086".parse().unwrap();
+        assert_eq!(vendor, VendorId(0x8086));
+        assert_eq!(vendor.to_string(), "8086");
+
+        let class: ClassCode = "0x0c0330".parse().unwrap();
+        assert_eq!(class, ClassCode::new(0x0c, 0x03, 0x30));
+        assert_eq!(class.to_string(), "0c0330");
+
+        let subsystem: SubsystemId = "17aa:2292".parse().unwrap();
+        assert_eq!(subsystem.vendor, VendorId(0x17aa));
+        assert_eq!(subsystem.to_string(), "17aa:2292");
+
+        assert_eq!("0x0a".parse::<Revision>().unwrap().to_string(), "0a");
+    }
+
+    #[test]
+    fn test_short_class_code() {
+        let class: ClassCode = "0604".parse().unwrap();
+        assert_eq!(class, ClassCode::new(0x06, 0x04, 0x00));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!("808".parse::<VendorId>().is_err());
+        assert!("80866".parse::<DeviceId>().is_err());
+        assert!("0c03300".parse::<ClassCode>().is_err());
+        assert!("17aa2292".parse::<SubsystemId>().is_err());
+        assert!("zz".parse::<Revision>().is_err());
+        assert!("0x0x8086".parse::<VendorId>().is_err());
+        assert!("0x0x0c03".parse::<ClassCode>().is_err());
+        assert_eq!("0X8086".parse::<VendorId>().unwrap(), VendorId(0x8086));
+        assert_eq!(
+            "0X0c03".parse::<ClassCode>().unwrap(),
+            ClassCode::new(0x0c, 0x03, 0x00)
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let subsystem = SubsystemId::new(VendorId(0x17aa), DeviceId(0x2292));
+        let json = serde_json::to_string(&subsystem).unwrap();
+        assert_eq!(json, "\"17aa:2292\"");
+        assert_eq!(
+            serde_json::from_str::<SubsystemId>(&json).unwrap(),
+            subsystem
+        );
+
+        let class = ClassCode::new(0x0c, 0x03, 0x30);
+        assert_eq!(serde_json::to_string(&class).unwrap(), "\"0c0330\"");
+        assert_eq!(
+            serde_json::from_str::<VendorId>("\"8086\"").unwrap(),
+            VendorId(0x8086)
+        );
+        assert!(serde_json::from_str::<VendorId>("32902").is_err());
+        assert!(serde_json::from_str::<DeviceId>("\"zzzz\"").is_err());
+    }
+}
@@ -18,14 +18,21 @@
 //! Information about `02:00.0` should be printed to the screen.
 //! There's always a chance that the address you provided to [`Device::new()`] could be non-existant, which will result
 //! in an empty object being returned.
+//! Use [`Device::try_new()`] instead if you need to know why, it returns an [`Error`] describing what went wrong.
 //!
 //! If you're unsure what PCI device you want to query, you can let [`Fetch`] do that for you.
 //! It can return a list of PCI devices with all their information.
+//!
+//! Names are resolved through [`pci_ids::PciIds`], which parses the pci.ids database only once.
 
 cfg_if::cfg_if! {
     if #[cfg(target_os = "linux")] {
         pub mod linux;
         pub type PCIDevice = linux::LinuxPCIDevice;
+        pub use linux::{
+            Devices, IommuGroup, KernelModule, NodeId, NodeKind, Order, Query, Sriov, SysfsWrite,
+            Topology, TopologyNode, TryDevices, UpstreamPath,
+        };
         pub type DeviceClass = device_class::DeviceClass;
     } else if #[cfg(target_os = "macos")] {
         pub mod macos;
@@ -42,7 +49,7 @@ cfg_if::cfg_if! {
 }
 
 /// A trait that provides the necessary methods which can initialize a single PCIDevice and fetch its information.
-pub trait Device {
+pub trait Device: Sized {
     /// This function returns a new instance of `PCIDevice` struct using the given `path`.
     ///
     /// # Examples
@@ -54,8 +61,49 @@ pub trait Device {
     /// let foo = PCIDevice::new("00:04.0");
     /// let bar = PCIDevice::new("0000:00:04.0");
     /// let baz = PCIDevice::new("/sys/bus/pci/devices/0000:00:04.0");
+    ///
+    /// // Devices outside of domain 0 need their domain to be specified.
+    /// let qux = PCIDevice::new("10000:e1:00.0");
+    /// ```
+    fn new(path: &str) -> Self {
+        Self::with_context(path, Context::global())
+    }
+
+    /// This function behaves like [`Device::new()`], except that it fails instead of
+    /// returning an incomplete object when the device doesn't exist, one of its attributes
+    /// can't be read, or the pci.ids database is missing.
+    ///
+    /// # Examples
+    ///
     /// ```
-    fn new(path: &str) -> Self;
+    /// use aparato::{Device, Error, PCIDevice};
+    ///
+    /// match PCIDevice::try_new("00:04.0") {
+    ///     Ok(device) => println!("{}", device.device_name()),
+    ///     Err(Error::NotFound(path)) => println!("{} does not exist", path.display()),
+    ///     Err(e) => println!("{}", e),
+    /// }
+    /// ```
+    fn try_new(path: &str) -> Result<Self> {
+        Self::try_with_context(path, Context::global())
+    }
+
+    /// This function behaves like [`Device::new()`], except that sysfs and the pci.ids database
+    /// are looked for at the locations given by `context`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use aparato::{Context, Device, PCIDevice};
+    ///
+    /// let context = Context::builder().sysfs_root("/host/sys").build();
+    /// let device = PCIDevice::with_context("00:04.0", &context);
+    /// ```
+    fn with_context(path: &str, context: &Context) -> Self;
+
+    /// This function behaves like [`Device::try_new()`], except that sysfs and the pci.ids database
+    /// are looked for at the locations given by `context`.
+    fn try_with_context(path: &str, context: &Context) -> Result<Self>;
 
     // Getters...
 
@@ -63,22 +111,54 @@ pub trait Device {
     fn path(&self) -> std::path::PathBuf;
 
     /// This function returns the `PCIDevice` address.
-    fn address(&self) -> String;
+    ///
+    /// The domain is omitted when it is 0, e.g. `00:02.0` rather than `0000:00:02.0`.
+    fn address(&self) -> String {
+        self.pci_address().to_short_string()
+    }
+
+    /// This function returns the `PCIDevice` address, including its domain.
+    fn pci_address(&self) -> PciAddress;
+
+    /// This function returns the `PCIDevice` class code.
+    fn class_code(&self) -> ClassCode;
+
+    /// This function returns the `PCIDevice` vendor ID.
+    fn vendor(&self) -> VendorId;
+
+    /// This function returns the `PCIDevice` device ID.
+    fn device(&self) -> DeviceId;
+
+    /// This function returns the `PCIDevice` revision.
+    fn revision_id(&self) -> Revision;
+
+    /// This function returns the `PCIDevice` subsystem vendor and device IDs.
+    fn subsystem(&self) -> SubsystemId;
 
     /// This function returns the `PCIDevice` class ID.
     ///
     /// The return value is a decoded hexadecimal value.
-    fn class_id(&self) -> Vec<u8>;
+    #[deprecated(note = "use `class_code()` instead")]
+    fn class_id(&self) -> Vec<u8> {
+        let class = self.class_code();
+        vec![class.class, class.subclass]
+    }
 
     /// This function returns the `PCIDevice` vendor ID.
     ///
     /// The return value is a decoded hexadecimal value.
-    fn vendor_id(&self) -> Vec<u8>;
+    #[deprecated(note = "use `vendor()` instead")]
+    fn vendor_id(&self) -> Vec<u8> {
+        self.vendor().0.to_be_bytes().to_vec()
+    }
 
     /// This function returns the `PCIDevice` device ID.
     ///
     /// The return value is a decoded hexadecimal value.
-    fn device_id(&self) -> Vec<u8>;
+    #[deprecated(note = "use `device()` instead")]
+    fn device_id(&self) -> Vec<u8> {
+        self.device().0.to_be_bytes().to_vec()
+    }
 
     /// This function returns the `PCIDevice` NUMA node.
     fn numa_node(&self) -> isize;
@@ -89,6 +169,17 @@ pub trait Device {
     /// This function returns the `PCIDevice` subclass name.
     fn subclass_name(&self) -> String;
 
+    /// This function returns the `PCIDevice` programming interface,
+    /// which tells apart devices of the same subclass, e.g. xHCI and EHCI USB controllers.
+    fn prog_if(&self) -> u8 {
+        self.class_code().prog_if
+    }
+
+    /// This function returns the `PCIDevice` programming interface name, e.g. `XHCI`.
+    ///
+    /// Most subclasses don't define any programming interface, in which case the name is empty.
+    fn prog_if_name(&self) -> String;
+
     /// This function returns the `PCIDevice` vendor name.
     fn vendor_name(&self) -> String;
 
@@ -98,26 +189,35 @@ pub trait Device {
     /// This function returns whether the `PCIDevice` is enabled.
     fn enabled(&self) -> bool;
 
-    /// This function returns whether the `PCIDevice` is enabled.
+    /// This function returns whether the `PCIDevice` is allowed to enter the D3cold power state.
     fn d3cold_allowed(&self) -> bool;
 
-    /// This function returns whether the `PCIDevice` is enabled.
+    /// This function returns the `PCIDevice` revision.
     ///
     /// The return value is a decoded hexadecimal value.
-    fn revision(&self) -> Vec<u8>;
+    #[deprecated(note = "use `revision_id()` instead")]
+    fn revision(&self) -> Vec<u8> {
+        vec![self.revision_id().0]
+    }
 
-    /// This function returns the `PCIDevice` subsystem vendor.
+    /// This function returns the `PCIDevice` subsystem name.
     fn subsystem_name(&self) -> String;
 
-    /// This function returns the `PCIDevice` subsystem vendor.
+    /// This function returns the `PCIDevice` subsystem vendor ID.
     ///
     /// The return value is a decoded hexadecimal value.
-    fn subsystem_vendor_id(&self) -> Vec<u8>;
+    #[deprecated(note = "use `subsystem()` instead")]
+    fn subsystem_vendor_id(&self) -> Vec<u8> {
+        self.subsystem().vendor.0.to_be_bytes().to_vec()
+    }
 
-    /// This function returns the `PCIDevice` subsystem vendor.
+    /// This function returns the `PCIDevice` subsystem device ID.
     ///
     /// The return value is a decoded hexadecimal value.
-    fn subsystem_device_id(&self) -> Vec<u8>;
+    #[deprecated(note = "use `subsystem()` instead")]
+    fn subsystem_device_id(&self) -> Vec<u8> {
+        self.subsystem().device.0.to_be_bytes().to_vec()
+    }
 }
 
 pub(crate) mod private {
@@ -125,55 +225,58 @@ pub(crate) mod private {
         // This trait contains exclusively the setters.
 
         /// This function is reserved for use by some of the mods provided by `Fetcher`
-        fn reserved_new(path: &str) -> Self;
+        fn reserved_new(path: &str, context: &crate::Context) -> Self;
 
         /// Set the `path` field of the `PCIDevice`.
         fn set_path(&mut self, p: std::path::PathBuf);
 
         /// This function sets the `address` field of the `PCIDevice`
-        fn set_address(&mut self);
+        fn set_address(&mut self) -> crate::Result<()>;
 
         /// This function sets the `device_id` field of the `PCIDevice`
-        fn set_class_id(&mut self);
+        fn set_class_id(&mut self) -> crate::Result<()>;
 
         /// This function sets the `device_id` field of the `PCIDevice`
-        fn set_vendor_id(&mut self);
+        fn set_vendor_id(&mut self) -> crate::Result<()>;
 
         /// This function sets the `device_id` field of the `PCIDevice`
-        fn set_device_id(&mut self);
+        fn set_device_id(&mut self) -> crate::Result<()>;
 
         /// This function sets the `numa_node` field of the `PCIDevice`
-        fn set_numa_node(&mut self);
+        fn set_numa_node(&mut self) -> crate::Result<()>;
 
         /// This function sets the `class_name` field of the `PCIDevice`
         fn set_class_name(&mut self);
 
         /// This function sets the `subclass_name` field of the `PCIDevice`
-        fn set_subclass_name(&mut self);
+        fn set_subclass_name(&mut self, ids: &crate::pci_ids::PciIds);
+
+        /// This function sets the `prog_if_name` field of the `PCIDevice`
+        fn set_prog_if_name(&mut self, ids: &crate::pci_ids::PciIds);
 
         /// This function sets the `revision` field of the `PCIDevice`
-        fn set_revision(&mut self);
+        fn set_revision(&mut self) -> crate::Result<()>;
 
         /// This function sets the `enabled` field of the `PCIDevice`
-        fn set_enabled(&mut self);
+        fn set_enabled(&mut self) -> crate::Result<()>;
 
         /// This function sets the `d3cold_allowed` field of the `PCIDevice`
-        fn set_d3cold_allowed(&mut self);
+        fn set_d3cold_allowed(&mut self) -> crate::Result<()>;
 
         /// This function sets the `vendor_name` field of the `PCIDevice`
-        fn set_vendor_name(&mut self);
+        fn set_vendor_name(&mut self, ids: &crate::pci_ids::PciIds);
 
         /// This function sets the `device_name` field of the `PCIDevice`
-        fn set_device_name(&mut self);
+        fn set_device_name(&mut self, ids: &crate::pci_ids::PciIds);
 
         /// This function sets the `subsystem_vendor_id` field of the `PCIDevice`
-        fn set_subsystem_device_id(&mut self);
+        fn set_subsystem_device_id(&mut self) -> crate::Result<()>;
 
         /// This function sets the `subsystem_device_id` field of the `PCIDevice`
-        fn set_subsystem_vendor_id(&mut self);
+        fn set_subsystem_vendor_id(&mut self) -> crate::Result<()>;
 
         /// This function sets the `subsystem_name` field of the `PCIDevice`
-        fn set_subsystem_name(&mut self);
+        fn set_subsystem_name(&mut self, ids: &crate::pci_ids::PciIds);
     }
 }
 
@@ -198,7 +301,43 @@ pub trait Fetch {
     ///     println!("{}", device.class_name());    
     /// }
     /// ```
-    fn fetch(maximum_devices: Option<u8>) -> Vec<PCIDevice>;
+    fn fetch(maximum_devices: Option<u8>) -> Vec<PCIDevice> {
+        Self::fetch_with_context(Context::global(), maximum_devices)
+    }
+
+    /// This function behaves like [`Fetch::fetch()`], except that it fails
+    /// as soon as the information of one of the PCI devices can't be fetched.
+    ///
+    /// # Examples
+    /// ```
+    /// use aparato::{Fetch, PCIDevice};
+    ///
+    /// if let Err(e) = PCIDevice::try_fetch(None) {
+    ///     eprintln!("{}", e);
+    /// }
+    /// ```
+    fn try_fetch(maximum_devices: Option<u8>) -> Result<Vec<PCIDevice>> {
+        Self::try_fetch_with_context(Context::global(), maximum_devices)
+    }
+
+    /// This function behaves like [`Fetch::fetch()`], except that sysfs and the pci.ids database
+    /// are looked for at the locations given by `context`.
+    ///
+    /// # Examples
+    /// ```
+    /// use aparato::{Context, Fetch, PCIDevice};
+    ///
+    /// let context = Context::builder().sysfs_root("/host/sys").build();
+    /// let devices = PCIDevice::fetch_with_context(&context, None);
+    /// ```
+    fn fetch_with_context(context: &Context, maximum_devices: Option<u8>) -> Vec<PCIDevice>;
+
+    /// This function behaves like [`Fetch::try_fetch()`], except that sysfs and the pci.ids database
+    /// are looked for at the locations given by `context`.
+    fn try_fetch_with_context(
+        context: &Context,
+        maximum_devices: Option<u8>,
+    ) -> Result<Vec<PCIDevice>>;
 
     /// This function returns a **list** of available PCI devices of a specific class and their information.
     ///
@@ -244,5 +383,22 @@ pub trait Fetch {
     fn fetch_gpus(maximum_devices: Option<u8>) -> Vec<String>;
 }
 
+pub mod address;
+pub mod bar;
+pub mod config_space;
+pub mod context;
 pub mod device_class;
+pub mod error;
+#[cfg(target_os = "linux")]
 mod extra;
+pub mod ids;
+pub mod link;
+pub mod modalias;
+pub mod pci_ids;
+#[cfg(feature = "serde")]
+mod serde_string;
+
+pub use address::PciAddress;
+pub use context::Context;
+pub use error::{Error, Result};
+pub use ids::{ClassCode, DeviceId, Revision, SubsystemId, VendorId};
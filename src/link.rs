@@ -0,0 +1,232 @@
+//! This module contains the types describing the PCI Express link of a device.
+
+use crate::address::PciAddress;
+use crate::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// The generation of a PCI Express link, which determines its transfer rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PcieGen {
+    /// 2.5 GT/s.
+    Gen1,
+    /// 5 GT/s.
+    Gen2,
+    /// 8 GT/s.
+    Gen3,
+    /// 16 GT/s.
+    Gen4,
+    /// 32 GT/s.
+    Gen5,
+    /// 64 GT/s.
+    Gen6,
+    /// 128 GT/s.
+    Gen7,
+}
+
+/// The speed and width of a PCI Express link.
+///
+/// # Examples
+///
+/// ```
+/// use aparato::link::{LinkStatus, PcieGen};
+///
+/// let current = LinkStatus::new(PcieGen::Gen3, 8);
+/// let max = LinkStatus::new(PcieGen::Gen4, 16);
+/// assert!(current.is_below(&max));
+/// assert_eq!(current.to_string(), "8.0 GT/s x8");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LinkStatus {
+    pub speed: PcieGen,
+    /// The number of lanes.
+    pub width: u8,
+}
+
+/// The link of a device along the path to its root port, returned as part of a [LinkChain].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkHop {
+    pub address: PciAddress,
+    pub path: PathBuf,
+    /// The current state of the link, `None` if the link is down, e.g. because the device is in D3cold.
+    pub current: Option<LinkStatus>,
+    pub max: LinkStatus,
+}
+
+/// The links found between a device and its root port, the device's own link coming first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkChain {
+    pub hops: Vec<LinkHop>,
+}
+
+impl PcieGen {
+    /// This function returns the generation matching the given link speed encoding,
+    /// e.g. `3` for 8 GT/s, as found in the link registers of the configuration space.
+    pub fn from_encoding(encoding: u8) -> Option<Self> {
+        match encoding {
+            1 => Some(PcieGen::Gen1),
+            2 => Some(PcieGen::Gen2),
+            3 => Some(PcieGen::Gen3),
+            4 => Some(PcieGen::Gen4),
+            5 => Some(PcieGen::Gen5),
+            6 => Some(PcieGen::Gen6),
+            7 => Some(PcieGen::Gen7),
+            _ => None,
+        }
+    }
+
+    /// This function returns the transfer rate of a lane, in GT/s.
+    pub fn transfer_rate(&self) -> f32 {
+        match self {
+            PcieGen::Gen1 => 2.5,
+            PcieGen::Gen2 => 5.0,
+            PcieGen::Gen3 => 8.0,
+            PcieGen::Gen4 => 16.0,
+            PcieGen::Gen5 => 32.0,
+            PcieGen::Gen6 => 64.0,
+            PcieGen::Gen7 => 128.0,
+        }
+    }
+
+    /// This function returns the usable throughput of a lane in each direction, in MB/s,
+    /// once the encoding overhead is accounted for.
+    pub fn lane_throughput(&self) -> u32 {
+        match self {
+            PcieGen::Gen1 => 250,
+            PcieGen::Gen2 => 500,
+            PcieGen::Gen3 => 985,
+            PcieGen::Gen4 => 1969,
+            PcieGen::Gen5 => 3938,
+            PcieGen::Gen6 => 7563,
+            PcieGen::Gen7 => 15125,
+        }
+    }
+}
+
+impl LinkStatus {
+    /// This function returns a new `LinkStatus`.
+    pub fn new(speed: PcieGen, width: u8) -> Self {
+        LinkStatus { speed, width }
+    }
+
+    /// This function returns the usable throughput of the link in each direction, in MB/s.
+    pub fn throughput(&self) -> u32 {
+        self.speed.lane_throughput() * u32::from(self.width)
+    }
+
+    /// This function returns whether the link is slower or narrower than `other`.
+    pub fn is_below(&self, other: &LinkStatus) -> bool {
+        self.speed < other.speed || self.width < other.width
+    }
+}
+
+impl LinkHop {
+    /// This function returns whether the link runs below its capability, which a link that is down does.
+    pub fn is_degraded(&self) -> bool {
+        match self.current {
+            Some(current) => current.is_below(&self.max),
+            None => true,
+        }
+    }
+
+    /// This function returns the throughput of the link in each direction, in MB/s, `0` if the link is down.
+    pub fn throughput(&self) -> u32 {
+        self.current.map_or(0, |current| current.throughput())
+    }
+}
+
+impl LinkChain {
+    /// This function returns the link with the lowest throughput, which limits the throughput of the device.
+    ///
+    /// The link closest to the device wins ties.
+    pub fn bottleneck(&self) -> Option<&LinkHop> {
+        self.hops.iter().min_by_key(|hop| hop.throughput())
+    }
+
+    /// This function returns the links which run below their capability.
+    pub fn degraded(&self) -> impl Iterator<Item = &LinkHop> {
+        self.hops.iter().filter(|hop| hop.is_degraded())
+    }
+}
+
+impl fmt::Display for PcieGen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.1} GT/s", self.transfer_rate())
+    }
+}
+
+impl fmt::Display for LinkStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} x{}", self.speed, self.width)
+    }
+}
+
+impl FromStr for PcieGen {
+    type Err = Error;
+
+    /// This function accepts the speeds displayed by sysfs, e.g. `8.0 GT/s PCIe` or `8 GT/s`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rate = s.split_whitespace().next().unwrap_or_default();
+        match rate.strip_suffix(".0").unwrap_or(rate) {
+            "2.5" => Ok(PcieGen::Gen1),
+            "5" => Ok(PcieGen::Gen2),
+            "8" => Ok(PcieGen::Gen3),
+            "16" => Ok(PcieGen::Gen4),
+            "32" => Ok(PcieGen::Gen5),
+            "64" => Ok(PcieGen::Gen6),
+            "128" => Ok(PcieGen::Gen7),
+            _ => Err(Error::InvalidLinkSpeed(s.to_owned())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_speed() {
+        assert_eq!("2.5 GT/s PCIe".parse::<PcieGen>().unwrap(), PcieGen::Gen1);
+        assert_eq!("8 GT/s".parse::<PcieGen>().unwrap(), PcieGen::Gen3);
+        assert_eq!("32.0 GT/s PCIe".parse::<PcieGen>().unwrap(), PcieGen::Gen5);
+        assert_eq!("128.0 GT/s PCIe".parse::<PcieGen>().unwrap(), PcieGen::Gen7);
+        assert_eq!(PcieGen::from_encoding(7), Some(PcieGen::Gen7));
+        assert!("Unknown".parse::<PcieGen>().is_err());
+        assert_eq!(PcieGen::Gen4.to_string(), "16.0 GT/s");
+    }
+
+    #[test]
+    fn test_bottleneck() {
+        let hop = |address: &str, current, max| LinkHop {
+            address: address.parse().unwrap(),
+            path: PathBuf::new(),
+            current,
+            max,
+        };
+        let chain = LinkChain {
+            hops: vec![
+                hop(
+                    "02:00.0",
+                    Some(LinkStatus::new(PcieGen::Gen4, 4)),
+                    LinkStatus::new(PcieGen::Gen4, 4),
+                ),
+                hop(
+                    "01:00.0",
+                    Some(LinkStatus::new(PcieGen::Gen2, 8)),
+                    LinkStatus::new(PcieGen::Gen4, 16),
+                ),
+            ],
+        };
+
+        assert_eq!(
+            chain.bottleneck().unwrap().address.to_short_string(),
+            "01:00.0"
+        );
+        let degraded: Vec<String> = chain
+            .degraded()
+            .map(|hop| hop.address.to_short_string())
+            .collect();
+        assert_eq!(degraded, ["01:00.0"]);
+    }
+}
@@ -0,0 +1,232 @@
+use super::LinuxPCIDevice;
+use crate::error::{Error, Result};
+use crate::PciAddress;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// A write to a sysfs file, returned by the functions changing the driver of a device.
+///
+/// When the [`Context`](crate::Context) of the device is a dry run, nothing is written
+/// and the returned `SysfsWrite` describes what would have been.
+///
+/// # Examples
+///
+/// ```no_run
+/// use aparato::{Context, Device, PCIDevice};
+///
+/// let context = Context::builder().dry_run(true).build();
+/// let gpu = PCIDevice::with_context("01:00.0", &context);
+///
+/// // Prints e.g. "echo '0000:01:00.0' > /sys/bus/pci/drivers/nvidia/unbind".
+/// if let Some(write) = gpu.unbind().unwrap() {
+///     println!("{}", write);
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SysfsWrite {
+    pub path: PathBuf,
+    pub value: String,
+}
+
+impl LinuxPCIDevice {
+    /// This function returns the driver the device is forced to bind to, set through [`LinuxPCIDevice::set_driver_override()`],
+    /// or `None` if there isn't any.
+    pub fn driver_override(&self) -> Result<Option<String>> {
+        Ok(self
+            .read_optional_attribute("driver_override")?
+            .filter(|value| value != "(null)" && !value.is_empty()))
+    }
+
+    /// This function unbinds the device from its driver.
+    ///
+    /// `None` is returned, and nothing is written, if the device isn't bound to any driver.
+    pub fn unbind(&self) -> Result<Option<SysfsWrite>> {
+        match self.driver() {
+            Some(driver) => self.write_driver_attribute(&driver, "unbind").map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// This function binds the device to `driver`, e.g. `vfio-pci`, which must be loaded
+    /// and must not be bound to the device already.
+    pub fn bind(&self, driver: &str) -> Result<SysfsWrite> {
+        self.write_driver_attribute(driver, "bind")
+    }
+
+    /// This function makes `driver` the only driver the device can be bound to,
+    /// regardless of the devices the driver declares support for.
+    ///
+    /// The device isn't rebound, which requires unbinding it and then probing it or binding it to `driver`.
+    pub fn set_driver_override(&self, driver: &str) -> Result<SysfsWrite> {
+        self.sysfs_address()?;
+        self.write_sysfs(self.path.join("driver_override"), driver.to_owned())
+    }
+
+    /// This function lets the device be bound to any driver supporting it again.
+    pub fn clear_driver_override(&self) -> Result<SysfsWrite> {
+        self.sysfs_address()?;
+        self.write_sysfs(self.path.join("driver_override"), "\n".to_owned())
+    }
+
+    fn write_driver_attribute(&self, driver: &str, attribute: &str) -> Result<SysfsWrite> {
+        let address = self.sysfs_address()?;
+        let path = self
+            .context
+            .sysfs_root()
+            .join("bus/pci/drivers")
+            .join(driver)
+            .join(attribute);
+        self.write_sysfs(path, address.to_long_string())
+    }
+
+    /// This function returns the address of the device, taken from the name of its sysfs directory.
+    ///
+    /// The `address` field isn't trusted, since it's left to its default value, which is the address
+    /// of the host bridge, when the device couldn't be read.
+    fn sysfs_address(&self) -> Result<PciAddress> {
+        if !self.path.is_dir() {
+            return Err(Error::NotFound(self.path.clone()));
+        }
+        let name = self
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        name.parse()
+    }
+
+    pub(super) fn write_sysfs(&self, path: PathBuf, value: String) -> Result<SysfsWrite> {
+        if self.context.dry_run() {
+            // The file is still checked for, so that e.g. a driver which isn't loaded is reported.
+            std::fs::metadata(&path).map_err(|e| write_error(&path, e))?;
+        } else {
+            std::fs::OpenOptions::new()
+                .write(true)
+                .truncate(true)
+                .open(&path)
+                .and_then(|mut file| file.write_all(value.as_bytes()))
+                .map_err(|e| write_error(&path, e))?;
+        }
+        Ok(SysfsWrite { path, value })
+    }
+}
+
+impl fmt::Display for SysfsWrite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "echo '{}' > {}",
+            self.value.trim_end_matches('\n'),
+            self.path.display()
+        )
+    }
+}
+
+fn write_error(path: &std::path::Path, error: io::Error) -> Error {
+    match error.kind() {
+        io::ErrorKind::PermissionDenied => Error::PermissionDenied(path.to_path_buf()),
+        _ => Error::io(path, error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fake_sysfs::FakeSysfs;
+    use super::*;
+    use crate::context::Context;
+    use crate::Device;
+
+    #[test]
+    fn test_bind() {
+        let sysfs = FakeSysfs::new("bind");
+        let path = sysfs.add_device("0000:01:00.0", &[]);
+        sysfs.bind_driver(&path, "nouveau");
+        sysfs.add_driver("vfio-pci");
+        let device = LinuxPCIDevice::with_context("01:00.0", &sysfs.context());
+        let read = |path: &PathBuf| std::fs::read_to_string(path).unwrap();
+
+        assert_eq!(device.driver_override().unwrap(), None);
+        let write = device.set_driver_override("vfio-pci").unwrap();
+        assert_eq!(write.path, device.path().join("driver_override"));
+        assert_eq!(read(&write.path), "vfio-pci");
+        assert_eq!(
+            device.driver_override().unwrap().as_deref(),
+            Some("vfio-pci")
+        );
+
+        let write = device.unbind().unwrap().unwrap();
+        assert!(write.path.ends_with("bus/pci/drivers/nouveau/unbind"));
+        assert_eq!(read(&write.path), "0000:01:00.0");
+
+        let write = device.bind("vfio-pci").unwrap();
+        assert!(write.path.ends_with("bus/pci/drivers/vfio-pci/bind"));
+        assert_eq!(read(&write.path), "0000:01:00.0");
+
+        device.clear_driver_override().unwrap();
+        assert_eq!(device.driver_override().unwrap(), None);
+
+        assert!(matches!(
+            device.bind("not-loaded"),
+            Err(Error::Io { source, .. }) if source.kind() == io::ErrorKind::NotFound
+        ));
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let sysfs = FakeSysfs::new("dry-run");
+        let path = sysfs.add_device("0000:01:00.0", &[]);
+        sysfs.bind_driver(&path, "nouveau");
+        sysfs.add_driver("vfio-pci");
+        let context = Context::builder()
+            .sysfs_root(sysfs.context().sysfs_root())
+            .dry_run(true)
+            .build();
+        let device = LinuxPCIDevice::with_context("01:00.0", &context);
+
+        let write = device.unbind().unwrap().unwrap();
+        assert_eq!(
+            write.to_string(),
+            format!("echo '0000:01:00.0' > {}", write.path.display())
+        );
+        assert_eq!(std::fs::read_to_string(&write.path).unwrap(), "");
+
+        device.set_driver_override("vfio-pci").unwrap();
+        assert_eq!(device.driver_override().unwrap(), None);
+        assert!(device.bind("not-loaded").is_err());
+
+        sysfs.add_device("0000:02:00.0", &[]);
+        let unbound = LinuxPCIDevice::with_context("02:00.0", &context);
+        assert_eq!(unbound.unbind().unwrap(), None);
+    }
+
+    #[test]
+    fn test_bind_unknown_device() {
+        let sysfs = FakeSysfs::new("bind-unknown-device");
+        sysfs.add_device("0000:00:00.0", &[]);
+        sysfs.add_driver("vfio-pci");
+        let context = Context::builder()
+            .sysfs_root(sysfs.context().sysfs_root())
+            .dry_run(true)
+            .build();
+
+        // The address of the device is left to its default, 0000:00:00.0, which must not be written.
+        let typo = LinuxPCIDevice::with_context("0b:00.0", &context);
+        assert!(matches!(typo.bind("vfio-pci"), Err(Error::NotFound(_))));
+        assert!(matches!(
+            typo.set_driver_override("vfio-pci"),
+            Err(Error::NotFound(_))
+        ));
+        assert!(matches!(
+            typo.clear_driver_override(),
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_permission_denied() {
+        let path = PathBuf::from("/sys/bus/pci/drivers/nvidia/unbind");
+        let error = write_error(&path, io::Error::from(io::ErrorKind::PermissionDenied));
+        assert!(matches!(error, Error::PermissionDenied(p) if p == path));
+    }
+}
@@ -0,0 +1,192 @@
+use super::LinuxPCIDevice;
+use crate::address::PciAddress;
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::private::Properties;
+use std::path::{Path, PathBuf};
+
+/// The order in which the PCI devices are enumerated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Order {
+    /// Devices are ordered by their address, i.e. by domain, bus, device and function, like `lspci` does.
+    #[default]
+    Address,
+    /// Devices are ordered depth-first, every bridge being followed by the devices sitting behind it,
+    /// like `lspci -t` does.
+    Topology,
+}
+
+/// A lazy iterator over the PCI devices, returned by [`LinuxPCIDevice::iter()`].
+///
+/// Devices are returned in [`Order::Address`] unless another order is requested.
+/// The information of a device is only fetched once the iterator reaches it,
+/// devices whose information can't be fetched are returned incomplete.
+pub struct Devices {
+    entries: Entries,
+}
+
+/// A lazy iterator over the PCI devices, returned by [`LinuxPCIDevice::try_iter()`].
+///
+/// Unlike [`Devices`], it returns an error for every device whose information can't be fetched.
+/// Devices are returned in [`Order::Address`] unless another order is requested.
+pub struct TryDevices {
+    entries: Entries,
+}
+
+/// The entries of the directory holding the PCI devices.
+struct Entries {
+    context: Context,
+    path: PathBuf,
+    order: Order,
+    state: State,
+}
+
+enum State {
+    Unopened,
+    Open(std::vec::IntoIter<Result<PathBuf>>),
+    Done,
+}
+
+impl Entries {
+    fn new(context: &Context) -> Self {
+        Entries {
+            context: context.clone(),
+            path: context.pci_devices_path(),
+            order: Order::default(),
+            state: State::Unopened,
+        }
+    }
+
+    /// This function returns the sorted entries of the directory.
+    ///
+    /// Sorting requires every entry to be known, but the devices themselves are still fetched lazily.
+    fn read(&self) -> std::io::Result<Vec<Result<PathBuf>>> {
+        let mut entries: Vec<(Vec<PciAddress>, Result<PathBuf>)> = std::fs::read_dir(&self.path)?
+            .map(|entry| match entry {
+                Ok(entry) => {
+                    let path = entry.path();
+                    (sort_key(&path, self.order), Ok(path))
+                }
+                Err(e) => (Vec::new(), Err(Error::io(&self.path, e))),
+            })
+            .collect();
+
+        // Entries without an address come after every device, the sort being stable,
+        // they keep their relative order.
+        entries.sort_by(|(a, _), (b, _)| (a.is_empty(), a).cmp(&(b.is_empty(), b)));
+        Ok(entries.into_iter().map(|(_, entry)| entry).collect())
+    }
+}
+
+/// This function returns the key by which the device located at `path` is sorted.
+///
+/// In topology order, the key is the chain of addresses leading from the host bridge to the device,
+/// the key of a bridge is thus a prefix of the keys of the devices behind it.
+fn sort_key(path: &Path, order: Order) -> Vec<PciAddress> {
+    match order {
+        Order::Address => path.to_string_lossy().parse().into_iter().collect(),
+        Order::Topology => match std::fs::canonicalize(path) {
+            Ok(canonical) => canonical
+                .components()
+                .filter_map(|c| c.as_os_str().to_str()?.parse().ok())
+                .collect(),
+            Err(_) => path.to_string_lossy().parse().into_iter().collect(),
+        },
+    }
+}
+
+impl Iterator for Entries {
+    type Item = Result<PathBuf>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // The directory is only opened once the first device is requested.
+        if let State::Unopened = self.state {
+            match self.read() {
+                Ok(entries) => self.state = State::Open(entries.into_iter()),
+                Err(e) => {
+                    self.state = State::Done;
+                    return Some(Err(Error::io(&self.path, e)));
+                }
+            }
+        }
+
+        match &mut self.state {
+            State::Open(entries) => entries.next(),
+            _ => None,
+        }
+    }
+}
+
+impl Devices {
+    pub(crate) fn new(context: &Context) -> Self {
+        Devices {
+            entries: Entries::new(context),
+        }
+    }
+
+    /// This function returns the iterator, enumerating the devices in the given order.
+    pub fn order(mut self, order: Order) -> Self {
+        self.entries.order = order;
+        self
+    }
+
+    /// This function returns an iterator over devices whose path is the only information known,
+    /// their attributes are left for the caller to fetch.
+    pub(crate) fn blanks(self) -> impl Iterator<Item = LinuxPCIDevice> {
+        let context = self.entries.context.clone();
+        self.entries
+            .filter_map(|entry| entry.ok())
+            .map(move |path| LinuxPCIDevice::blank(&context, path))
+    }
+
+    /// This function returns an iterator over devices whose class is the only information fetched,
+    /// the remainder of the information can be fetched through `populate()` later on.
+    pub(crate) fn reserved(self) -> impl Iterator<Item = LinuxPCIDevice> {
+        let context = self.entries.context.clone();
+        self.entries
+            .filter_map(|entry| entry.ok())
+            .map(move |path| LinuxPCIDevice::reserved_new(&path.to_string_lossy(), &context))
+    }
+}
+
+impl Iterator for Devices {
+    type Item = LinuxPCIDevice;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Ok(path) = self.entries.next()? {
+                let mut device = LinuxPCIDevice::blank(&self.entries.context, path);
+                let _ = device.populate(false);
+                return Some(device);
+            }
+        }
+    }
+}
+
+impl TryDevices {
+    pub(crate) fn new(context: &Context) -> Self {
+        TryDevices {
+            entries: Entries::new(context),
+        }
+    }
+
+    /// This function returns the iterator, enumerating the devices in the given order.
+    pub fn order(mut self, order: Order) -> Self {
+        self.entries.order = order;
+        self
+    }
+}
+
+impl Iterator for TryDevices {
+    type Item = Result<LinuxPCIDevice>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let path = match self.entries.next()? {
+            Ok(path) => path,
+            Err(e) => return Some(Err(e)),
+        };
+
+        let mut device = LinuxPCIDevice::blank(&self.entries.context, path);
+        Some(device.populate(true).map(|_| device))
+    }
+}
@@ -0,0 +1,163 @@
+use super::LinuxPCIDevice;
+use crate::error::Result;
+use crate::extra::basename;
+use crate::modalias::Modalias;
+
+/// The kernel module providing the driver of a device, returned by [`LinuxPCIDevice::kernel_module()`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KernelModule {
+    /// The name of the module, e.g. `nvidia`.
+    pub name: String,
+    /// The version the module declares, which many in-tree modules don't.
+    pub version: Option<String>,
+    /// The checksum of the sources the module was built from.
+    pub srcversion: Option<String>,
+}
+
+impl LinuxPCIDevice {
+    /// This function returns the name of the driver bound to the device, e.g. `vfio-pci`,
+    /// or `None` if the device isn't bound to any driver.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use aparato::{Device, PCIDevice};
+    ///
+    /// let gpu = PCIDevice::new("01:00.0");
+    /// match gpu.driver().as_deref() {
+    ///     Some("nvidia") => {}
+    ///     Some(driver) => println!("{} is bound to {} instead of nvidia", gpu.address(), driver),
+    ///     None => println!("{} isn't bound to any driver", gpu.address()),
+    /// }
+    /// ```
+    pub fn driver(&self) -> Option<String> {
+        basename(&std::fs::read_link(self.path.join("driver")).ok()?)
+    }
+
+    /// This function returns the kernel module providing the driver of the device.
+    ///
+    /// `None` is returned if the device isn't bound to any driver,
+    /// or if its driver is built into the kernel and thus not part of any module.
+    pub fn kernel_module(&self) -> Option<KernelModule> {
+        let module = std::fs::read_link(self.path.join("driver/module")).ok()?;
+        let name = basename(&module)?;
+
+        // The module is looked for in the context rather than through the link,
+        // which keeps working when sysfs is mounted elsewhere.
+        let path = self.context.sysfs_root().join("module").join(&name);
+        let read = |attribute: &str| -> Option<String> {
+            let value = std::fs::read_to_string(path.join(attribute)).ok()?;
+            Some(value.trim_end().to_owned())
+        };
+
+        Some(KernelModule {
+            version: read("version"),
+            srcversion: read("srcversion"),
+            name,
+        })
+    }
+
+    /// This function returns the modalias of the device, which the kernel matches drivers against.
+    pub fn modalias(&self) -> Result<Modalias> {
+        let value = self.read_attribute("modalias")?;
+        value
+            .parse()
+            .map_err(|_| self.malformed_attribute("modalias", value))
+    }
+
+    /// This function returns the kernel modules able to drive the device, according to the aliases
+    /// found in the modules.alias file of the [`Context`](crate::Context).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use aparato::{Device, Fetch, PCIDevice};
+    ///
+    /// for device in PCIDevice::fetch(None) {
+    ///     if device.driver().is_none() {
+    ///         let modules = device.candidate_modules().unwrap();
+    ///         println!("{} could be driven by {}", device.address(), modules.join(", "));
+    ///     }
+    /// }
+    /// ```
+    pub fn candidate_modules(&self) -> Result<Vec<String>> {
+        let modalias = self.modalias()?;
+        let aliases = self.context.module_aliases()?;
+        Ok(aliases
+            .modules(&modalias)
+            .into_iter()
+            .map(str::to_owned)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fake_sysfs::FakeSysfs;
+    use super::*;
+    use crate::Device;
+
+    #[test]
+    fn test_driver() {
+        let sysfs = FakeSysfs::new("driver");
+        let gpu = sysfs.add_device("0000:01:00.0", &[]);
+        let nic = sysfs.add_device("0000:02:00.0", &[]);
+        sysfs.add_device("0000:03:00.0", &[]);
+        sysfs.bind_driver(&gpu, "nvidia");
+        sysfs.add_module(
+            "nvidia",
+            "nvidia",
+            &[("version", "550.54.14"), ("srcversion", "1A2B3C4D5E6F")],
+        );
+        sysfs.bind_driver(&nic, "e1000e");
+        let context = sysfs.context();
+
+        let gpu = LinuxPCIDevice::with_context("01:00.0", &context);
+        assert_eq!(gpu.driver().as_deref(), Some("nvidia"));
+        assert_eq!(
+            gpu.kernel_module(),
+            Some(KernelModule {
+                name: "nvidia".to_owned(),
+                version: Some("550.54.14".to_owned()),
+                srcversion: Some("1A2B3C4D5E6F".to_owned()),
+            })
+        );
+
+        // The driver is built into the kernel.
+        let nic = LinuxPCIDevice::with_context("02:00.0", &context);
+        assert_eq!(nic.driver().as_deref(), Some("e1000e"));
+        assert_eq!(nic.kernel_module(), None);
+
+        let unbound = LinuxPCIDevice::with_context("03:00.0", &context);
+        assert_eq!(unbound.driver(), None);
+        assert_eq!(unbound.kernel_module(), None);
+    }
+
+    #[test]
+    fn test_candidate_modules() {
+        let sysfs = FakeSysfs::new("candidate-modules");
+        let modalias = "pci:v00008086d00001234sv00008086sd00000001bc03sc00i00";
+        sysfs.add_device("0000:00:02.0", &[("modalias", modalias)]);
+        sysfs.add_device("0000:00:03.0", &[("modalias", "pci:v8086")]);
+        let aliases = "\
+alias pci:v00008086d*sv*sd*bc03sc*i* i915
+alias pci:v000010DEd*sv*sd*bc03sc*i* nouveau
+"
+        .parse()
+        .unwrap();
+        let context = crate::Context::builder()
+            .sysfs_root(sysfs.context().sysfs_root())
+            .module_aliases(aliases)
+            .build();
+
+        let gpu = LinuxPCIDevice::with_context("00:02.0", &context);
+        assert_eq!(gpu.modalias().unwrap().to_string(), modalias);
+        assert_eq!(gpu.candidate_modules().unwrap(), ["i915"]);
+
+        let malformed = LinuxPCIDevice::with_context("00:03.0", &context);
+        assert!(matches!(
+            malformed.candidate_modules(),
+            Err(crate::Error::MalformedAttribute { .. })
+        ));
+    }
+}
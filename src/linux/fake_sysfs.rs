@@ -0,0 +1,152 @@
+//! A fake sysfs tree which the tests can populate with PCI devices.
+
+use crate::context::Context;
+use crate::pci_ids::PciIds;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The pci.ids database matching the default attributes of the fake devices.
+pub(crate) const PCI_IDS: &str = "\
+8086  Intel Corporation
+\t1234  Fake Controller
+\t\t8086 0001  Fake Subsystem
+C 03  Display controller
+\t00  VGA compatible controller
+\t\t00  VGA controller
+C 0c  Serial bus controller
+\t03  USB controller
+\t\t20  EHCI
+\t\t30  XHCI
+";
+
+const DEFAULT_ATTRIBUTES: &[(&str, &str)] = &[
+    ("class", "0x030000"),
+    ("vendor", "0x8086"),
+    ("device", "0x1234"),
+    ("revision", "0x01"),
+    ("subsystem_vendor", "0x8086"),
+    ("subsystem_device", "0x0001"),
+    ("enable", "1"),
+    ("numa_node", "-1"),
+    ("d3cold_allowed", "1"),
+    ("driver_override", "(null)"),
+];
+
+pub(crate) struct FakeSysfs {
+    root: PathBuf,
+}
+
+impl FakeSysfs {
+    /// This function creates an empty sysfs tree, `name` must be unique to the test.
+    pub(crate) fn new(name: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("aparato-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("bus/pci/devices")).unwrap();
+        fs::create_dir_all(root.join("devices")).unwrap();
+        FakeSysfs { root }
+    }
+
+    /// This function returns a context pointing at the fake tree, along with the fake pci.ids database.
+    pub(crate) fn context(&self) -> Context {
+        Context::builder()
+            .sysfs_root(&self.root)
+            .pci_ids(PCI_IDS.parse::<PciIds>().unwrap())
+            .build()
+    }
+
+    /// This function adds a device sitting on a root bus, e.g. `0000:00:02.0`.
+    pub(crate) fn add_device(&self, address: &str, attributes: &[(&str, &str)]) -> PathBuf {
+        let (bus, _) = address.rsplit_once(':').unwrap();
+        let host_bridge = self.root.join("devices").join(format!("pci{}", bus));
+        self.add_device_at(&host_bridge, address, attributes)
+    }
+
+    /// This function adds a device sitting behind the bridge located at `bridge`.
+    pub(crate) fn add_device_behind(
+        &self,
+        bridge: &Path,
+        address: &str,
+        attributes: &[(&str, &str)],
+    ) -> PathBuf {
+        self.add_device_at(bridge, address, attributes)
+    }
+
+    /// This function writes an attribute of the device located at `path`.
+    pub(crate) fn write(&self, path: &Path, name: &str, value: &str) {
+        let file = path.join(name);
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(file, format!("{}\n", value)).unwrap();
+    }
+
+    /// This function binds the device located at `path` to `driver`, the same way the kernel links them.
+    pub(crate) fn bind_driver(&self, path: &Path, driver: &str) {
+        let driver_path = self.add_driver(driver);
+        std::os::unix::fs::symlink(&driver_path, path.join("driver")).unwrap();
+    }
+
+    /// This function adds a loaded driver, along with its empty `bind` and `unbind` files.
+    pub(crate) fn add_driver(&self, driver: &str) -> PathBuf {
+        let driver_path = self.root.join("bus/pci/drivers").join(driver);
+        fs::create_dir_all(&driver_path).unwrap();
+        for file in &["bind", "unbind"] {
+            fs::write(driver_path.join(file), "").unwrap();
+        }
+        driver_path
+    }
+
+    /// This function adds a kernel module along with its attributes, and makes it the module of `driver`.
+    pub(crate) fn add_module(&self, driver: &str, name: &str, attributes: &[(&str, &str)]) {
+        let module = self.root.join("module").join(name);
+        fs::create_dir_all(&module).unwrap();
+        for (attribute, value) in attributes {
+            self.write(&module, attribute, value);
+        }
+
+        let driver_path = self.root.join("bus/pci/drivers").join(driver);
+        fs::create_dir_all(&driver_path).unwrap();
+        std::os::unix::fs::symlink(&module, driver_path.join("module")).unwrap();
+    }
+
+    /// This function adds the virtual function `index` of the physical function located at `pf`,
+    /// which sits on the same bus.
+    pub(crate) fn add_virtual_function(&self, pf: &Path, index: u16, address: &str) -> PathBuf {
+        let vf = self.add_device_at(pf.parent().unwrap(), address, &[]);
+        std::os::unix::fs::symlink(&vf, pf.join(format!("virtfn{}", index))).unwrap();
+        std::os::unix::fs::symlink(pf, vf.join("physfn")).unwrap();
+        vf
+    }
+
+    /// This function adds an IOMMU group holding the devices located at `devices`.
+    pub(crate) fn add_iommu_group(&self, id: u32, devices: &[&Path]) {
+        let group = self.root.join("kernel/iommu_groups").join(id.to_string());
+        fs::create_dir_all(group.join("devices")).unwrap();
+        for device in devices {
+            let address = device.file_name().unwrap();
+            std::os::unix::fs::symlink(device, group.join("devices").join(address)).unwrap();
+            std::os::unix::fs::symlink(&group, device.join("iommu_group")).unwrap();
+        }
+    }
+
+    fn add_device_at(&self, parent: &Path, address: &str, attributes: &[(&str, &str)]) -> PathBuf {
+        let path = parent.join(address);
+        fs::create_dir_all(&path).unwrap();
+
+        for (name, value) in DEFAULT_ATTRIBUTES {
+            if !attributes.iter().any(|(n, _)| n == name) {
+                self.write(&path, name, value);
+            }
+        }
+        for (name, value) in attributes {
+            self.write(&path, name, value);
+        }
+
+        std::os::unix::fs::symlink(&path, self.root.join("bus/pci/devices").join(address)).unwrap();
+        path
+    }
+}
+
+impl Drop for FakeSysfs {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
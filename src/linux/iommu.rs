@@ -0,0 +1,175 @@
+use super::LinuxPCIDevice;
+use crate::address::PciAddress;
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::Device;
+use std::path::PathBuf;
+
+/// A group of devices the IOMMU can't isolate from each other,
+/// which must thus be assigned to a virtual machine together.
+///
+/// # Examples
+///
+/// ```no_run
+/// use aparato::{Device, IommuGroup};
+///
+/// for group in IommuGroup::all().unwrap() {
+///     println!("IOMMU group {}:", group.id);
+///     for device in &group.devices {
+///         println!("\t{} {}", device.address(), device.device_name());
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct IommuGroup {
+    pub id: u32,
+    pub path: PathBuf,
+    /// The PCI devices of the group, ordered by address.
+    pub devices: Vec<LinuxPCIDevice>,
+}
+
+impl IommuGroup {
+    /// This function returns every IOMMU group, ordered by ID.
+    ///
+    /// No group is returned if the IOMMU is disabled.
+    pub fn all() -> Result<Vec<IommuGroup>> {
+        Self::all_with_context(Context::global())
+    }
+
+    /// This function behaves like [`IommuGroup::all()`], except that sysfs and the pci.ids database
+    /// are looked for at the locations given by `context`.
+    pub fn all_with_context(context: &Context) -> Result<Vec<IommuGroup>> {
+        let path = groups_path(context);
+        let entries = match std::fs::read_dir(&path) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(Error::io(path, e)),
+        };
+
+        let mut groups = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|e| Error::io(&path, e))?;
+            if let Some(id) = entry.file_name().to_str().and_then(|id| id.parse().ok()) {
+                groups.push(Self::with_context(id, context)?);
+            }
+        }
+        groups.sort_by_key(|group| group.id);
+        Ok(groups)
+    }
+
+    /// This function returns the IOMMU group with the given ID.
+    pub fn with_context(id: u32, context: &Context) -> Result<IommuGroup> {
+        let path = groups_path(context).join(id.to_string());
+        let devices_path = path.join("devices");
+        let entries = std::fs::read_dir(&devices_path).map_err(|e| Error::io(&devices_path, e))?;
+
+        // Groups may hold devices of other buses, which are left out.
+        let mut addresses = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|e| Error::io(&devices_path, e))?;
+            if let Some(address) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.parse::<PciAddress>().ok())
+            {
+                addresses.push(address);
+            }
+        }
+        addresses.sort();
+
+        let devices = addresses
+            .iter()
+            .map(|address| LinuxPCIDevice::with_context(&address.to_long_string(), context))
+            .collect();
+        Ok(IommuGroup { id, path, devices })
+    }
+}
+
+impl LinuxPCIDevice {
+    /// This function returns the IOMMU group of the device, or `None` if the IOMMU is disabled.
+    pub fn iommu_group(&self) -> Result<Option<IommuGroup>> {
+        let id = match std::fs::read_link(self.path.join("iommu_group")) {
+            Ok(link) => link,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::io(self.path.join("iommu_group"), e)),
+        };
+        let id = id
+            .file_name()
+            .and_then(|id| id.to_str())
+            .and_then(|id| id.parse().ok())
+            .ok_or_else(|| {
+                self.malformed_attribute("iommu_group", id.to_string_lossy().into_owned())
+            })?;
+        IommuGroup::with_context(id, &self.context).map(Some)
+    }
+
+    /// This function returns the other devices of the IOMMU group of the device,
+    /// which must be passed through to a virtual machine along with it.
+    pub fn iommu_group_peers(&self) -> Result<Vec<LinuxPCIDevice>> {
+        let group = match self.iommu_group()? {
+            Some(group) => group,
+            None => return Ok(Vec::new()),
+        };
+        let address = self.pci_address();
+        Ok(group
+            .devices
+            .into_iter()
+            .filter(|device| device.pci_address() != address)
+            .collect())
+    }
+}
+
+fn groups_path(context: &Context) -> PathBuf {
+    context.sysfs_root().join("kernel/iommu_groups")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fake_sysfs::FakeSysfs;
+    use super::*;
+
+    #[test]
+    fn test_iommu_groups() {
+        let sysfs = FakeSysfs::new("iommu-groups");
+        let root_port = sysfs.add_device("0000:00:01.0", &[]);
+        let gpu = sysfs.add_device_behind(&root_port, "0000:01:00.0", &[]);
+        let audio = sysfs.add_device_behind(&root_port, "0000:01:00.1", &[]);
+        let usb = sysfs.add_device("0000:00:14.0", &[]);
+        sysfs.add_iommu_group(2, &[&root_port]);
+        sysfs.add_iommu_group(10, &[&audio, &gpu]);
+        sysfs.add_iommu_group(9, &[&usb]);
+        let context = sysfs.context();
+
+        let groups = IommuGroup::all_with_context(&context).unwrap();
+        let ids: Vec<u32> = groups.iter().map(|g| g.id).collect();
+        assert_eq!(ids, [2, 9, 10]);
+        let addresses: Vec<String> = groups[2].devices.iter().map(|d| d.address()).collect();
+        assert_eq!(addresses, ["01:00.0", "01:00.1"]);
+        assert_eq!(groups[2].devices[0].device_name(), "Fake Controller");
+
+        let gpu = LinuxPCIDevice::with_context("01:00.0", &context);
+        assert_eq!(gpu.iommu_group().unwrap().unwrap().id, 10);
+        let peers: Vec<String> = gpu
+            .iommu_group_peers()
+            .unwrap()
+            .iter()
+            .map(|d| d.address())
+            .collect();
+        assert_eq!(peers, ["01:00.1"]);
+
+        let usb = LinuxPCIDevice::with_context("00:14.0", &context);
+        assert!(usb.iommu_group_peers().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_iommu_disabled() {
+        let sysfs = FakeSysfs::new("iommu-disabled");
+        sysfs.add_device("0000:00:02.0", &[]);
+        let context = sysfs.context();
+
+        assert!(IommuGroup::all_with_context(&context).unwrap().is_empty());
+        let device = LinuxPCIDevice::with_context("00:02.0", &context);
+        assert!(device.iommu_group().unwrap().is_none());
+        assert!(device.iommu_group_peers().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,210 @@
+use super::LinuxPCIDevice;
+use crate::address::PciAddress;
+use crate::error::{Error, Result};
+use crate::link::{LinkChain, LinkHop, LinkStatus, PcieGen};
+use std::path::PathBuf;
+
+impl LinuxPCIDevice {
+    /// This function returns the speed and width the link of the device currently runs at.
+    ///
+    /// Conventional PCI devices have no link, the attributes are thus missing and an error is returned.
+    pub fn current_link(&self) -> Result<LinkStatus> {
+        self.read_link("current_link_speed", "current_link_width")
+    }
+
+    /// This function returns the highest speed and width the link of the device supports.
+    pub fn max_link(&self) -> Result<LinkStatus> {
+        self.read_link("max_link_speed", "max_link_width")
+    }
+
+    /// This function returns the links between the device and its root port,
+    /// from which the bottleneck and the degraded links can be found.
+    ///
+    /// Bridges without a link, such as conventional PCI bridges, are skipped,
+    /// while links that are down are kept, without a current state.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use aparato::{Device, PCIDevice};
+    ///
+    /// let gpu = PCIDevice::new("01:00.0");
+    /// let chain = gpu.link_chain().unwrap();
+    ///
+    /// for hop in chain.degraded() {
+    ///     match hop.current {
+    ///         Some(current) => println!("{} runs at {} instead of {}", hop.address, current, hop.max),
+    ///         None => println!("The link of {} is down", hop.address),
+    ///     }
+    /// }
+    /// if let Some(hop) = chain.bottleneck() {
+    ///     println!("The throughput is limited to {} MB/s by {}", hop.throughput(), hop.address);
+    /// }
+    /// ```
+    pub fn link_chain(&self) -> Result<LinkChain> {
+        let mut hops = Vec::new();
+        let mut path =
+            Some(std::fs::canonicalize(&self.path).map_err(|e| Error::io(&self.path, e))?);
+
+        while let Some(current) = path {
+            let device = LinuxPCIDevice::blank(&self.context, current);
+            let max = device.read_optional_link("max_link_speed", "max_link_width")?;
+            if let Some(max) = max {
+                hops.push(LinkHop {
+                    address: device.path.to_string_lossy().parse()?,
+                    path: device.path.clone(),
+                    current: device
+                        .read_optional_link("current_link_speed", "current_link_width")?,
+                    max,
+                });
+            }
+            path = device.parent_path();
+        }
+
+        Ok(LinkChain { hops })
+    }
+
+    /// This function returns the canonical path of the bridge the device sits behind,
+    /// or `None` if the device sits on a root bus.
+    pub(crate) fn parent_path(&self) -> Option<PathBuf> {
+        let canonical = std::fs::canonicalize(&self.path).ok()?;
+        let parent = canonical.parent()?;
+        parent
+            .to_string_lossy()
+            .parse::<PciAddress>()
+            .ok()
+            .map(|_| parent.to_path_buf())
+    }
+
+    fn read_link(&self, speed: &str, width: &str) -> Result<LinkStatus> {
+        let speed_value = self.read_attribute(speed)?;
+        let speed = match speed_value.parse::<PcieGen>() {
+            Ok(gen) => gen,
+            Err(_) => return Err(self.malformed_attribute(speed, speed_value)),
+        };
+        Ok(LinkStatus::new(speed, self.read_parsed_attribute(width)?))
+    }
+
+    /// This function behaves like `read_link`, except that `None` is returned if the device has no link,
+    /// or if the speed is unknown, which the kernel reports for a link that is down,
+    /// as `Unknown` or, on older kernels, as `Unknown speed`.
+    fn read_optional_link(&self, speed: &str, width: &str) -> Result<Option<LinkStatus>> {
+        match self.read_optional_attribute(speed)? {
+            Some(value) if !value.starts_with("Unknown") => self.read_link(speed, width).map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fake_sysfs::FakeSysfs;
+    use super::*;
+    use crate::Device;
+
+    fn link(
+        speed: &str,
+        width: &str,
+        max_speed: &str,
+        max_width: &str,
+    ) -> Vec<(&'static str, String)> {
+        vec![
+            ("current_link_speed", format!("{} GT/s PCIe", speed)),
+            ("current_link_width", width.to_owned()),
+            ("max_link_speed", format!("{} GT/s PCIe", max_speed)),
+            ("max_link_width", max_width.to_owned()),
+        ]
+    }
+
+    fn attributes<'a>(link: &'a [(&'static str, String)]) -> Vec<(&'static str, &'a str)> {
+        link.iter()
+            .map(|(name, value)| (*name, value.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_link_chain() {
+        let sysfs = FakeSysfs::new("link-chain");
+        let root_port = link("16.0", "16", "16.0", "16");
+        let root_port = sysfs.add_device("0000:00:01.0", &attributes(&root_port));
+        // A conventional PCI bridge, which has no link.
+        let bridge = sysfs.add_device_behind(&root_port, "0000:01:00.0", &[]);
+        let switch = link("2.5", "16", "16.0", "16");
+        let switch = sysfs.add_device_behind(&bridge, "0000:02:00.0", &attributes(&switch));
+        let gpu = link("16.0", "8", "16.0", "16");
+        sysfs.add_device_behind(&switch, "0000:03:00.0", &attributes(&gpu));
+        let context = sysfs.context();
+
+        let gpu = LinuxPCIDevice::with_context("03:00.0", &context);
+        assert_eq!(
+            gpu.current_link().unwrap(),
+            LinkStatus::new(PcieGen::Gen4, 8)
+        );
+        assert_eq!(gpu.max_link().unwrap(), LinkStatus::new(PcieGen::Gen4, 16));
+
+        let chain = gpu.link_chain().unwrap();
+        let addresses: Vec<String> = chain
+            .hops
+            .iter()
+            .map(|h| h.address.to_short_string())
+            .collect();
+        assert_eq!(addresses, ["03:00.0", "02:00.0", "00:01.0"]);
+        assert_eq!(
+            chain.bottleneck().unwrap().address.to_short_string(),
+            "02:00.0"
+        );
+        assert_eq!(chain.degraded().count(), 2);
+    }
+
+    #[test]
+    fn test_no_link() {
+        let sysfs = FakeSysfs::new("no-link");
+        let path = sysfs.add_device("0000:00:1f.0", &[]);
+        let device = LinuxPCIDevice::with_context("00:1f.0", &sysfs.context());
+        assert!(device.current_link().is_err());
+        assert!(device.link_chain().unwrap().hops.is_empty());
+
+        sysfs.write(&path, "current_link_speed", "Unknown");
+        sysfs.write(&path, "current_link_width", "0");
+        assert!(matches!(
+            device.current_link(),
+            Err(Error::MalformedAttribute { .. })
+        ));
+    }
+
+    #[test]
+    fn test_link_down() {
+        let sysfs = FakeSysfs::new("link-down");
+        let root_port = link("128.0", "16", "128.0", "16");
+        let root_port = sysfs.add_device("0000:00:01.0", &attributes(&root_port));
+        // The device is in D3cold, its link is down.
+        let gpu = [
+            ("current_link_speed", "Unknown"),
+            ("current_link_width", "0"),
+            ("max_link_speed", "128.0 GT/s PCIe"),
+            ("max_link_width", "16"),
+        ];
+        let path = sysfs.add_device_behind(&root_port, "0000:01:00.0", &gpu);
+        let gpu = LinuxPCIDevice::with_context("01:00.0", &sysfs.context());
+
+        let chain = gpu.link_chain().unwrap();
+        assert_eq!(chain.hops.len(), 2);
+        assert_eq!(chain.hops[0].current, None);
+        assert_eq!(
+            chain.hops[1].current,
+            Some(LinkStatus::new(PcieGen::Gen7, 16))
+        );
+        let degraded: Vec<String> = chain
+            .degraded()
+            .map(|hop| hop.address.to_short_string())
+            .collect();
+        assert_eq!(degraded, ["01:00.0"]);
+        assert_eq!(chain.bottleneck().unwrap().throughput(), 0);
+
+        // Older kernels spell the speed of a link that is down differently.
+        sysfs.write(&path, "current_link_speed", "Unknown speed");
+        let chain = gpu.link_chain().unwrap();
+        assert_eq!(chain.hops.len(), 2);
+        assert_eq!(chain.hops[0].current, None);
+    }
+}
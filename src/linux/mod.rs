@@ -1,96 +1,279 @@
 #![doc(hidden)]
 #![allow(unused_variables)]
+use crate::address::PciAddress;
+use crate::bar::{self, Bar};
+use crate::config_space::{BridgeInfo, Capability, ConfigSpace, ExtendedCapability};
+use crate::context::Context;
 use crate::device_class::*;
-use crate::extra::*;
+use crate::error::{Error, Result};
+use crate::ids::{ClassCode, DeviceId, Revision, SubsystemId, VendorId};
+use crate::pci_ids::PciIds;
 use crate::private::Properties;
 use crate::Device;
 use crate::Fetch;
 use std::path::PathBuf;
+use std::str::FromStr;
 
-/// This is where PCI devices are located.
-const PATH_TO_PCI_DEVICES: &str = "/sys/bus/pci/devices/";
-/// This is where the pci.ids file is located.
-const PATH_TO_PCI_IDS: &str = "/usr/share/hwdata/pci.ids";
-
-#[derive(Debug)]
+mod bind;
+mod devices;
+mod driver;
+#[cfg(test)]
+mod fake_sysfs;
+mod iommu;
+mod link;
+mod query;
+mod sriov;
+mod topology;
+mod upstream;
+
+pub use bind::SysfsWrite;
+pub use devices::{Devices, Order, TryDevices};
+pub use driver::KernelModule;
+pub use iommu::IommuGroup;
+pub use query::Query;
+pub use sriov::Sriov;
+pub use topology::{NodeId, NodeKind, Topology, TopologyNode};
+pub use upstream::UpstreamPath;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LinuxPCIDevice {
     path: PathBuf,
-    address: String,
-    class_id: Vec<u8>,
+    address: PciAddress,
+    class_id: ClassCode,
     class_name: String,
     subclass_name: String,
-    vendor_id: Vec<u8>,
+    prog_if_name: String,
+    vendor_id: VendorId,
     vendor_name: String,
-    device_id: Vec<u8>,
+    device_id: DeviceId,
     device_name: String,
-    revision: Vec<u8>,
+    revision: Revision,
     numa_node: isize,
     enabled: bool,
     d3cold_allowed: bool,
-    subsystem_vendor_id: Vec<u8>,
-    subsystem_device_id: Vec<u8>,
+    subsystem_id: SubsystemId,
     subsystem_name: String,
+    /// The context is left out, deserialized devices belong to the default one.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    context: Context,
 }
 
-impl Device for LinuxPCIDevice {
-    fn new(path: &str) -> Self {
-        let mut device: LinuxPCIDevice = Default::default();
-        let mut path_vec = [path].to_vec();
-
-        // One of the following two conditions will try to autocomplete the path of the
-        // PCI device if the one provided doesn't point to a real path in the filesystem.
-        if !PathBuf::from(path_vec.concat()).is_dir() {
-            // e.g. 0000:00:00.0 ->  /sys/bus/pci/devices/0000:00:00.0
-            path_vec.insert(0, PATH_TO_PCI_DEVICES);
-            device.set_path(PathBuf::from(path_vec.concat()));
-            if !PathBuf::from(path_vec.concat()).is_dir() {
-                // e.g. 00:00.0      ->  /sys/bus/pci/devices/0000:00:00.0
-                let mut id = path.to_owned();
-                id.insert_str(0, "0000:");
-                std::mem::swap(&mut path_vec[1], &mut id.as_str());
-                device.set_path(PathBuf::from(path_vec.concat()));
+/// This function tries to autocomplete the path of the PCI device
+/// if the one provided doesn't point to a real path in the filesystem.
+fn resolve_path(context: &Context, path: &str) -> PathBuf {
+    let given = PathBuf::from(path);
+    if given.is_dir() {
+        return given;
+    }
+
+    // e.g. 00:00.0      ->  /sys/bus/pci/devices/0000:00:00.0
+    //      0000:00:00.0 ->  /sys/bus/pci/devices/0000:00:00.0
+    match path.parse::<PciAddress>() {
+        Ok(address) => context.pci_devices_path().join(address.to_long_string()),
+        Err(_) => context.pci_devices_path().join(path),
+    }
+}
+
+impl LinuxPCIDevice {
+    /// This function returns a lazy iterator over the available PCI devices.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use aparato::{Device, PCIDevice, VendorId};
+    ///
+    /// // Only the devices preceding the first Intel device are fetched.
+    /// let first_intel = PCIDevice::iter().find(|d| d.vendor() == VendorId(0x8086));
+    /// ```
+    pub fn iter() -> Devices {
+        Self::iter_with_context(Context::global())
+    }
+
+    /// This function behaves like [`LinuxPCIDevice::iter()`], except that sysfs and the pci.ids database
+    /// are looked for at the locations given by `context`.
+    pub fn iter_with_context(context: &Context) -> Devices {
+        Devices::new(context)
+    }
+
+    /// This function returns a lazy iterator over the available PCI devices,
+    /// which returns an error for each device whose information can't be fetched.
+    pub fn try_iter() -> TryDevices {
+        Self::try_iter_with_context(Context::global())
+    }
+
+    /// This function behaves like [`LinuxPCIDevice::try_iter()`], except that sysfs and the pci.ids database
+    /// are looked for at the locations given by `context`.
+    pub fn try_iter_with_context(context: &Context) -> TryDevices {
+        TryDevices::new(context)
+    }
+
+    /// This function returns the configuration space of the device.
+    ///
+    /// Unless the caller is privileged, only the 64-byte header can be read,
+    /// see [`ConfigSpace::is_truncated()`].
+    pub fn config_space(&self) -> Result<ConfigSpace> {
+        let path = self.path.join("config");
+        let data = std::fs::read(&path).map_err(|e| Error::io(path, e))?;
+        ConfigSpace::new(data)
+    }
+
+    /// This function returns the implemented BARs of the device, including its expansion ROM.
+    pub fn bars(&self) -> Result<Vec<Bar>> {
+        let resource = self.read_attribute("resource")?;
+        match bar::parse_resource(&resource) {
+            Some(bars) => Ok(bars),
+            None => Err(self.malformed_attribute("resource", resource)),
+        }
+    }
+
+    /// This function returns the standard capabilities of the device, as listed by `lspci -v`.
+    ///
+    /// The capabilities live past the header, reading them thus requires privileges.
+    pub fn capabilities(&self) -> Result<Vec<Capability>> {
+        self.config_space()?.capabilities().collect()
+    }
+
+    /// This function returns the PCI Express extended capabilities of the device, as listed by `lspci -vvv`.
+    ///
+    /// Conventional PCI devices have none, reading them requires privileges as well.
+    pub fn extended_capabilities(&self) -> Result<Vec<ExtendedCapability>> {
+        self.config_space()?.extended_capabilities().collect()
+    }
+
+    /// This function returns the buses, windows and port type of the device if it's a PCI-to-PCI bridge,
+    /// or `None` otherwise.
+    ///
+    /// The port type needs the capabilities, which are only readable with privileges.
+    pub fn bridge_info(&self) -> Result<Option<BridgeInfo>> {
+        Ok(self.config_space()?.bridge_info())
+    }
+
+    /// This function returns an empty device located at `path`, belonging to the given context.
+    fn blank(context: &Context, path: PathBuf) -> Self {
+        let mut device = LinuxPCIDevice {
+            context: context.clone(),
+            ..Default::default()
+        };
+        device.set_path(path);
+        device
+    }
+
+    /// This function reads every attribute of the device and resolves its names.
+    ///
+    /// When `strict` is set, the first failure is returned,
+    /// otherwise the fields that couldn't be read are left untouched.
+    fn populate(&mut self, strict: bool) -> Result<()> {
+        let setters: [fn(&mut Self) -> Result<()>; 10] = [
+            Self::set_address,
+            Self::set_class_id,
+            Self::set_vendor_id,
+            Self::set_device_id,
+            Self::set_numa_node,
+            Self::set_enabled,
+            Self::set_d3cold_allowed,
+            Self::set_revision,
+            Self::set_subsystem_device_id,
+            Self::set_subsystem_vendor_id,
+        ];
+
+        for setter in setters.iter() {
+            match setter(self) {
+                Err(e) if strict => return Err(e),
+                _ => {}
+            }
+        }
+        self.set_class_name();
+
+        let context = self.context.clone();
+        match context.pci_ids() {
+            Ok(ids) => {
+                self.set_device_name(ids);
+                self.set_vendor_name(ids);
+                self.set_subsystem_name(ids);
+                self.set_subclass_name(ids);
+                self.set_prog_if_name(ids);
             }
-        } else {
-            device.set_path(PathBuf::from(path_vec.concat()));
+            Err(e) if strict => return Err(e),
+            Err(_) => {}
         }
 
-        device.set_address();
-        device.set_class_id();
-        device.set_vendor_id();
-        device.set_device_id();
-        device.set_numa_node();
-        device.set_enabled();
-        device.set_d3cold_allowed();
-        device.set_revision();
-        device.set_subsystem_device_id();
-        device.set_subsystem_vendor_id();
-        device.set_class_name();
-        device.set_device_name();
-        device.set_vendor_name();
-        device.set_subsystem_name();
-        device.set_subclass_name();
+        Ok(())
+    }
+
+    /// This function returns the trimmed content of the given attribute of the device.
+    fn read_attribute(&self, name: &str) -> Result<String> {
+        let path = self.path.join(name);
+        match std::fs::read_to_string(&path) {
+            Ok(value) => Ok(value.trim_end().to_owned()),
+            Err(e) => Err(Error::io(path, e)),
+        }
+    }
 
+    /// This function behaves like `read_attribute`, except that a missing attribute isn't an error.
+    fn read_optional_attribute(&self, name: &str) -> Result<Option<String>> {
+        match self.read_attribute(name) {
+            Ok(value) => Ok(Some(value)),
+            Err(Error::Io { source, .. }) if source.kind() == std::io::ErrorKind::NotFound => {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// This function returns the parsed content of the given attribute, e.g. `0x8086` for `vendor`.
+    fn read_parsed_attribute<T: FromStr>(&self, name: &str) -> Result<T> {
+        let value = self.read_attribute(name)?;
+        match value.parse() {
+            Ok(parsed) => Ok(parsed),
+            Err(_) => Err(self.malformed_attribute(name, value)),
+        }
+    }
+
+    fn malformed_attribute(&self, name: &str, value: String) -> Error {
+        Error::MalformedAttribute {
+            path: self.path.join(name),
+            value,
+        }
+    }
+}
+
+impl Device for LinuxPCIDevice {
+    fn with_context(path: &str, context: &Context) -> Self {
+        let mut device = LinuxPCIDevice::blank(context, resolve_path(context, path));
+        // Whatever can't be read is left to its default value.
+        let _ = device.populate(false);
         device
     }
 
+    fn try_with_context(path: &str, context: &Context) -> Result<Self> {
+        let mut device = LinuxPCIDevice::blank(context, resolve_path(context, path));
+        if !device.path.is_dir() {
+            return Err(Error::NotFound(device.path));
+        }
+
+        device.populate(true)?;
+        Ok(device)
+    }
+
     fn path(&self) -> PathBuf {
         self.path.to_owned()
     }
 
-    fn address(&self) -> String {
-        self.address.to_owned()
+    fn pci_address(&self) -> PciAddress {
+        self.address
     }
 
-    fn class_id(&self) -> Vec<u8> {
-        self.class_id.to_owned()
+    fn class_code(&self) -> ClassCode {
+        self.class_id
     }
 
-    fn vendor_id(&self) -> Vec<u8> {
-        self.vendor_id.to_owned()
+    fn vendor(&self) -> VendorId {
+        self.vendor_id
     }
 
-    fn device_id(&self) -> Vec<u8> {
-        self.device_id.to_owned()
+    fn device(&self) -> DeviceId {
+        self.device_id
     }
 
     fn numa_node(&self) -> isize {
@@ -105,6 +288,10 @@ impl Device for LinuxPCIDevice {
         self.subclass_name.to_owned()
     }
 
+    fn prog_if_name(&self) -> String {
+        self.prog_if_name.to_owned()
+    }
+
     fn vendor_name(&self) -> String {
         self.vendor_name.to_owned()
     }
@@ -121,49 +308,28 @@ impl Device for LinuxPCIDevice {
         self.d3cold_allowed
     }
 
-    fn revision(&self) -> Vec<u8> {
-        self.revision.to_owned()
+    fn revision_id(&self) -> Revision {
+        self.revision
     }
 
     fn subsystem_name(&self) -> String {
         self.subsystem_name.to_owned()
     }
 
-    fn subsystem_vendor_id(&self) -> Vec<u8> {
-        self.subsystem_vendor_id.to_owned()
-    }
-
-    fn subsystem_device_id(&self) -> Vec<u8> {
-        self.subsystem_device_id.to_owned()
+    fn subsystem(&self) -> SubsystemId {
+        self.subsystem_id
     }
 }
 
 impl Properties for LinuxPCIDevice {
-    fn reserved_new(path: &str) -> Self {
-        let mut device: LinuxPCIDevice = Default::default();
-        let mut path_vec = [path].to_vec();
-
-        // One of the following two conditions will try to autocomplete the path of the
-        // PCI device if the one provided doesn't point to a real path in the filesystem.
-        if !PathBuf::from(path_vec.concat()).is_dir() {
-            // e.g. 0000:00:00.0 ->  /sys/bus/pci/devices/0000:00:00.0
-            path_vec.insert(0, PATH_TO_PCI_DEVICES);
-            device.set_path(PathBuf::from(path_vec.concat()));
-            if !PathBuf::from(path_vec.concat()).is_dir() {
-                // e.g. 00:00.0  ->  /sys/bus/pci/devices/0000:00:00.0
-                let mut id = path.to_owned();
-                id.insert_str(0, "0000:");
-                std::mem::swap(&mut path_vec[1], &mut id.as_str());
-                device.set_path(PathBuf::from(path_vec.concat()));
-            }
-        } else {
-            device.set_path(PathBuf::from(path_vec.concat()));
-        }
+    fn reserved_new(path: &str, context: &Context) -> Self {
+        let mut device = LinuxPCIDevice::blank(context, resolve_path(context, path));
 
         // reserved_new tries to fetch the least amount of data at first.
         // All the other fields can be populated later on.
-        device.set_class_id();
-        device.set_class_name();
+        if device.set_class_id().is_ok() {
+            device.set_class_name();
+        }
 
         device
     }
@@ -172,86 +338,54 @@ impl Properties for LinuxPCIDevice {
         self.path = p;
     }
 
-    fn set_address(&mut self) {
-        self.address = basename(
-            self.path()
-                .as_path()
-                .display()
-                .to_string()
-                .replace("0000:", ""),
-        );
+    fn set_address(&mut self) -> Result<()> {
+        self.address = self.path.to_string_lossy().parse()?;
+        Ok(())
     }
 
-    fn set_class_id(&mut self) {
-        if let Ok(str) = std::fs::read_to_string(&self.path.join("class")) {
-            let new_str = str.trim_start_matches("0x").trim_end_matches("\n");
-            if let Ok(decoded) = hex::decode(&new_str[..4]) {
-                self.class_id = decoded;
-            }
-        }
+    fn set_class_id(&mut self) -> Result<()> {
+        self.class_id = self.read_parsed_attribute("class")?;
+        Ok(())
     }
 
-    fn set_vendor_id(&mut self) {
-        if let Ok(str) = std::fs::read_to_string(&self.path.join("vendor")) {
-            let new_str = str.trim_start_matches("0x").trim_end_matches("\n");
-            if let Ok(decoded) = hex::decode(&new_str) {
-                self.vendor_id = decoded;
-            }
-        }
+    fn set_vendor_id(&mut self) -> Result<()> {
+        self.vendor_id = self.read_parsed_attribute("vendor")?;
+        Ok(())
     }
 
-    fn set_device_id(&mut self) {
-        if let Ok(str) = std::fs::read_to_string(&self.path.join("device")) {
-            let new_str = str.trim_start_matches("0x").trim_end_matches("\n");
-            if let Ok(decoded) = hex::decode(&new_str) {
-                self.device_id = decoded;
-            }
-        }
+    fn set_device_id(&mut self) -> Result<()> {
+        self.device_id = self.read_parsed_attribute("device")?;
+        Ok(())
     }
 
-    fn set_revision(&mut self) {
-        if let Ok(str) = std::fs::read_to_string(&self.path.join("revision")) {
-            let new_str = str.trim_start_matches("0x").trim_end_matches("\n");
-            if let Ok(decoded) = hex::decode(&new_str) {
-                self.revision = decoded;
-            }
-        }
+    fn set_revision(&mut self) -> Result<()> {
+        self.revision = self.read_parsed_attribute("revision")?;
+        Ok(())
     }
 
-    fn set_numa_node(&mut self) {
-        if let Ok(str) = std::fs::read_to_string(&self.path.join("numa_node")) {
-            let prefixless = str.trim_start_matches("0x").trim_end_matches("\n");
-            if let Ok(v) = prefixless.parse::<isize>() {
-                self.numa_node = v;
-            }
+    fn set_numa_node(&mut self) -> Result<()> {
+        // numa_node is missing on kernels built without NUMA support.
+        if let Some(value) = self.read_optional_attribute("numa_node")? {
+            self.numa_node = value
+                .parse::<isize>()
+                .map_err(|_| self.malformed_attribute("numa_node", value))?;
         }
+        Ok(())
     }
 
-    fn set_subsystem_vendor_id(&mut self) {
-        if let Ok(str) = std::fs::read_to_string(&self.path.join("subsystem_vendor")) {
-            let new_str = str.trim_start_matches("0x").trim_end_matches("\n");
-            if let Ok(decoded) = hex::decode(&new_str) {
-                self.subsystem_vendor_id = decoded;
-            }
-        }
+    fn set_subsystem_vendor_id(&mut self) -> Result<()> {
+        self.subsystem_id.vendor = self.read_parsed_attribute("subsystem_vendor")?;
+        Ok(())
     }
 
-    fn set_subsystem_device_id(&mut self) {
-        if let Ok(str) = std::fs::read_to_string(&self.path.join("subsystem_device")) {
-            let new_str = str.trim_start_matches("0x").trim_end_matches("\n");
-            if let Ok(decoded) = hex::decode(&new_str) {
-                self.subsystem_device_id = decoded;
-            }
-        }
+    fn set_subsystem_device_id(&mut self) -> Result<()> {
+        self.subsystem_id.device = self.read_parsed_attribute("subsystem_device")?;
+        Ok(())
     }
 
     fn set_class_name(&mut self) {
-        if self.class_id.is_empty() {
-            return;
-        }
-
         // Associate class_id with class_name
-        self.class_name = match &self.class_id[0] {
+        self.class_name = match self.class_id.class {
             1 => DeviceClass::MassStorageController.to_string(),
             2 => DeviceClass::NetworkController.to_string(),
             3 => DeviceClass::DisplayController.to_string(),
@@ -271,124 +405,60 @@ impl Properties for LinuxPCIDevice {
             17 => DeviceClass::SignalProcessingController.to_string(),
             18 => DeviceClass::ProcessingAccelerator.to_string(),
             19 => DeviceClass::NonEssentialInstrumentation.to_string(),
-            46 => DeviceClass::Coprocessor.to_string(),
+            64 => DeviceClass::Coprocessor.to_string(),
             255 => DeviceClass::Unassigned.to_string(),
             _ => DeviceClass::Unclassified.to_string(),
         }
     }
 
-    fn set_subclass_name(&mut self) {
-        if self.class_id.is_empty() {
-            return;
-        }
-
-        if let Ok(lines) = read_lines(PATH_TO_PCI_IDS) {
-            let class: [u8; 1] = [self.class_id[0]];
-            let encoded_class = hex::encode(&class);
-            let subclass: [u8; 1] = [self.class_id[1]];
-            let encoded_subclass = hex::encode(&subclass);
-            let mut found_my_class = false;
-
-            for line in lines {
-                if let Ok(l) = &line {
-                    if l.is_empty() || l.starts_with("#") {
-                        continue;
-                    } else if l.starts_with("C") && l.contains(&encoded_class) {
-                        found_my_class = true;
-                    } else if l.starts_with("\t") && l.contains(&encoded_subclass) && found_my_class
-                    {
-                        self.subclass_name = l.replace(&encoded_subclass, "").trim().to_owned();
-                        return;
-                    }
-                }
-            }
+    fn set_subclass_name(&mut self, ids: &PciIds) {
+        if let Some(name) = ids.subclass_name(self.class_id.class, self.class_id.subclass) {
+            self.subclass_name = name.to_owned();
         }
     }
 
-    fn set_vendor_name(&mut self) {
-        if self.vendor_id.is_empty() {
-            return;
-        }
-
-        if let Ok(lines) = read_lines(PATH_TO_PCI_IDS) {
-            let ven = hex::encode(self.vendor_id.to_owned());
-
-            for line in lines {
-                if let Ok(l) = &line {
-                    if l.len() == 0 || l.starts_with("#") || l.starts_with("C") {
-                        continue;
-                    } else if !l.starts_with("\t") && l.contains(&ven) {
-                        self.vendor_name = l.replace(&ven, "").trim().to_owned();
-                        return;
-                    }
-                }
-            }
+    fn set_prog_if_name(&mut self, ids: &PciIds) {
+        let class = self.class_id;
+        if let Some(name) = ids.prog_if_name(class.class, class.subclass, class.prog_if) {
+            self.prog_if_name = name.to_owned();
         }
     }
 
-    fn set_device_name(&mut self) {
-        if self.device_id.is_empty() {
-            return;
+    fn set_vendor_name(&mut self, ids: &PciIds) {
+        if let Some(name) = ids.vendor_name(self.vendor_id.0) {
+            self.vendor_name = name.to_owned();
         }
+    }
 
-        if let Ok(lines) = read_lines(PATH_TO_PCI_IDS) {
-            let dev = hex::encode(self.device_id.to_owned());
-            for line in lines {
-                if let Ok(l) = &line {
-                    if l.len() == 0 || l.starts_with("#") || l.starts_with("C") {
-                        continue;
-                    } else if l.starts_with("\t") && l.contains(&dev) {
-                        self.device_name = l.replace(&dev, "").trim().to_owned();
-                        return;
-                    }
-                }
-            }
+    fn set_device_name(&mut self, ids: &PciIds) {
+        if let Some(name) = ids.device_name(self.vendor_id.0, self.device_id.0) {
+            self.device_name = name.to_owned();
         }
     }
 
-    fn set_subsystem_name(&mut self) {
-        if self.subsystem_device_id.is_empty() {
-            return;
-        }
-
-        if let Ok(lines) = read_lines(PATH_TO_PCI_IDS) {
-            let sub_dev = hex::encode(self.subsystem_device_id.to_owned());
-            let sub_ven = hex::encode(self.subsystem_vendor_id.to_owned());
-
-            for line in lines {
-                if let Ok(l) = &line {
-                    if l.len() == 0 && l.starts_with("#") && l.starts_with("C") {
-                        continue;
-                    } else if l.starts_with("\t\t") && l.contains(&sub_dev) && l.contains(&sub_ven)
-                    {
-                        self.subsystem_name = l
-                            .replace(&sub_dev, "")
-                            .replace(&sub_ven, "")
-                            .trim()
-                            .to_owned();
-                        return;
-                    }
-                }
-            }
+    fn set_subsystem_name(&mut self, ids: &PciIds) {
+        let subsystem = self.subsystem_id;
+        if let Some(name) = ids.subsystem_name(
+            self.vendor_id.0,
+            self.device_id.0,
+            subsystem.vendor.0,
+            subsystem.device.0,
+        ) {
+            self.subsystem_name = name.to_owned();
         }
     }
 
-    fn set_enabled(&mut self) {
-        if let Ok(str) = std::fs::read_to_string(&self.path.join("enable")) {
-            match &str[..] {
-                "0\n" => self.enabled = false,
-                _ => self.enabled = true,
-            }
-        }
+    fn set_enabled(&mut self) -> Result<()> {
+        self.enabled = self.read_attribute("enable")? != "0";
+        Ok(())
     }
 
-    fn set_d3cold_allowed(&mut self) {
-        if let Ok(str) = std::fs::read_to_string(&self.path.join("d3cold_allowed")) {
-            match &str[..] {
-                "0\n" => self.d3cold_allowed = false,
-                _ => self.d3cold_allowed = true,
-            }
+    fn set_d3cold_allowed(&mut self) -> Result<()> {
+        // d3cold_allowed is missing on kernels built without runtime power management.
+        if let Some(value) = self.read_optional_attribute("d3cold_allowed")? {
+            self.d3cold_allowed = value != "0";
         }
+        Ok(())
     }
 }
 
@@ -396,144 +466,106 @@ impl Default for LinuxPCIDevice {
     fn default() -> Self {
         LinuxPCIDevice {
             path: PathBuf::new(),
-            address: String::new(),
+            address: PciAddress::default(),
             class_name: String::new(),
             subclass_name: String::new(),
+            prog_if_name: String::new(),
             vendor_name: String::new(),
             device_name: String::new(),
             subsystem_name: String::new(),
-            class_id: vec![],
-            subsystem_vendor_id: vec![],
-            subsystem_device_id: vec![],
-            device_id: vec![],
-            revision: vec![],
-            vendor_id: vec![],
+            class_id: ClassCode::default(),
+            subsystem_id: SubsystemId::default(),
+            device_id: DeviceId::default(),
+            revision: Revision::default(),
+            vendor_id: VendorId::default(),
             numa_node: -1,
             d3cold_allowed: false,
             enabled: false,
+            context: Context::global().clone(),
         }
     }
 }
 
 impl Fetch for LinuxPCIDevice {
-    fn fetch(maximum_devices: Option<u8>) -> Vec<LinuxPCIDevice> {
-        let mut devices = Vec::new();
-        let entries = list_dir_entries(PATH_TO_PCI_DEVICES);
-        let mut i = 0u8;
-        for dir in entries {
-            if let Some(d) = dir.to_str() {
-                if let Some(m) = maximum_devices {
-                    i = i + 1;
-                    if i > m {
-                        continue;
-                    }
-                }
+    fn fetch_with_context(context: &Context, maximum_devices: Option<u8>) -> Vec<LinuxPCIDevice> {
+        LinuxPCIDevice::iter_with_context(context)
+            .take(limit(maximum_devices))
+            .collect()
+    }
 
-                let device = LinuxPCIDevice::new(d);
-                devices.push(device);
-            }
-        }
-        return devices;
+    fn try_fetch_with_context(
+        context: &Context,
+        maximum_devices: Option<u8>,
+    ) -> Result<Vec<LinuxPCIDevice>> {
+        LinuxPCIDevice::try_iter_with_context(context)
+            .take(limit(maximum_devices))
+            .collect()
     }
 
     fn fetch_by_class(class: DeviceClass, maximum_devices: Option<u8>) -> Vec<LinuxPCIDevice> {
-        let mut devices = Vec::new();
-        let dir_entries = list_dir_entries(PATH_TO_PCI_DEVICES);
-        let mut i = 0u8;
-
-        for dir in dir_entries {
-            if let Some(d) = dir.to_str() {
-                if let Some(m) = maximum_devices {
-                    i = i + 1;
-                    if i > m {
-                        continue;
-                    }
-                }
-
-                // We're using `PCIDevice::reserved_new()` to initialize a PCIDevice
-                // with as little data as possible to avoid performance issues.
-                let mut device = LinuxPCIDevice::reserved_new(d);
-                if device.class_name() == class.to_string() {
-                    // We can now proceed to get and set the rest of the data
-                    // after having confirmed that the current PCIDevice's class matches
-                    // that provided by the user through a variant of the `DeviceClass` enum.
-                    device.set_address();
-                    device.set_vendor_id();
-                    device.set_device_id();
-                    device.set_numa_node();
-                    device.set_enabled();
-                    device.set_d3cold_allowed();
-                    device.set_revision();
-                    device.set_subsystem_device_id();
-                    device.set_subsystem_vendor_id();
-                    device.set_device_name();
-                    device.set_vendor_name();
-                    device.set_subsystem_name();
-                    device.set_subclass_name();
-                    devices.push(device);
-                }
-            }
-        }
-
-        return devices;
+        // The devices only hold their class at first, which avoids fetching
+        // the information of the devices which don't match.
+        LinuxPCIDevice::iter()
+            .reserved()
+            .filter(|device| device.class_name() == class.to_string())
+            .map(|mut device| {
+                let _ = device.populate(false);
+                device
+            })
+            .take(limit(maximum_devices))
+            .collect()
     }
 
     fn fetch_gpus(maximum_devices: Option<u8>) -> Vec<String> {
-        let mut gpus: Vec<String> = vec![];
-        let devices: Vec<LinuxPCIDevice> = Vec::new();
-        let dir_entries = list_dir_entries(PATH_TO_PCI_DEVICES);
-        let mut i = 0u8;
-
-        for dir in dir_entries {
-            if let Some(d) = dir.to_str() {
-                if let Some(m) = maximum_devices {
-                    i = i + 1;
-                    if i > m {
-                        continue;
-                    }
+        let ids = Context::global().pci_ids().ok();
+
+        LinuxPCIDevice::iter()
+            .reserved()
+            .filter(|device| device.class_name() == DeviceClass::DisplayController.to_string())
+            // We're only going to return enabled gpus.
+            .filter_map(|mut device| match device.set_enabled() {
+                Ok(()) if device.enabled => Some(device),
+                _ => None,
+            })
+            .map(|mut device| {
+                let _ = device.set_vendor_id();
+                let _ = device.set_device_id();
+                if let Some(ids) = ids {
+                    device.set_device_name(ids);
+                    device.set_vendor_name(ids);
                 }
 
-                // We're using `PCIDevice::reserved_new()` to initialize a PCIDevice
-                // with as little data as possible to avoid performance issues.
-                let mut device = LinuxPCIDevice::reserved_new(d);
-                if device.class_name() == DeviceClass::DisplayController.to_string() {
-                    // We can now proceed to get and set the rest of the data
-                    // after having confirmed that the current PCIDevice's class matches
-                    // that provided by the user through a variant of the `DeviceClass` enum.
-                    device.set_enabled();
-                    // We're only going to return enabled gpus.
-                    if device.enabled {
-                        device.set_vendor_id();
-                        device.set_device_id();
-                        device.set_device_name();
-                        device.set_vendor_name();
-
-                        let whole_name = device.device_name();
-                        // Extracting text within brackets from device_name.
-                        if let Some(start_bytes) = whole_name.find("[") {
-                            if let Some(end_bytes) = whole_name.rfind("]") {
-                                device.device_name =
-                                    whole_name[start_bytes + 1..end_bytes].to_owned();
-                            }
-                        }
-
-                        if device.vendor_name().contains("Corporation") {
-                            device.vendor_name = device.vendor_name().replace(" Corporation", "");
-                        }
-
-                        let str = String::from(device.vendor_name + " " + &device.device_name);
-                        gpus.push(str);
+                let whole_name = device.device_name();
+                // Extracting text within brackets from device_name.
+                if let Some(start_bytes) = whole_name.find('[') {
+                    if let Some(end_bytes) = whole_name.rfind(']') {
+                        device.device_name = whole_name[start_bytes + 1..end_bytes].to_owned();
                     }
                 }
-            }
-        }
 
-        return gpus;
+                if device.vendor_name().contains("Corporation") {
+                    device.vendor_name = device.vendor_name().replace(" Corporation", "");
+                }
+
+                device.vendor_name + " " + &device.device_name
+            })
+            .take(limit(maximum_devices))
+            .collect()
+    }
+}
+
+/// This function converts the `maximum_devices` argument of `Fetch` into a number of devices,
+/// `None` and `Some(0)` both mean there's no limit.
+fn limit(maximum_devices: Option<u8>) -> usize {
+    match maximum_devices {
+        Some(m) if m > 0 => m as usize,
+        _ => usize::MAX,
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::fake_sysfs::FakeSysfs;
     use super::*;
 
     const PLACEHOLDER_PCI_DEVICE: &str = "00:00.0";
@@ -551,21 +583,24 @@ mod tests {
     }
 
     #[test]
+    #[allow(deprecated)]
     fn test_class_id() {
         let device = LinuxPCIDevice::new(PLACEHOLDER_PCI_DEVICE);
-        assert_ne!(device.device_id(), Vec::new());
+        assert!(!device.device_id().is_empty());
     }
 
     #[test]
+    #[allow(deprecated)]
     fn test_vendor_id() {
         let device = LinuxPCIDevice::new(PLACEHOLDER_PCI_DEVICE);
-        assert_ne!(device.vendor_id(), Vec::new());
+        assert!(!device.vendor_id().is_empty());
     }
 
     #[test]
+    #[allow(deprecated)]
     fn test_device_id() {
         let device = LinuxPCIDevice::new(PLACEHOLDER_PCI_DEVICE);
-        assert_ne!(device.device_id(), Vec::new());
+        assert!(!device.device_id().is_empty());
     }
 
     #[test]
@@ -575,21 +610,24 @@ mod tests {
     }
 
     #[test]
+    #[allow(deprecated)]
     fn test_revision() {
         let device = LinuxPCIDevice::new(PLACEHOLDER_PCI_DEVICE);
-        assert_ne!(device.revision(), Vec::new());
+        assert!(!device.revision().is_empty());
     }
 
     #[test]
+    #[allow(deprecated)]
     fn test_subsystem_vendor_id() {
         let device = LinuxPCIDevice::new(PLACEHOLDER_PCI_DEVICE);
-        assert_ne!(device.subsystem_vendor_id(), Vec::new());
+        assert!(!device.subsystem_vendor_id().is_empty());
     }
 
     #[test]
+    #[allow(deprecated)]
     fn test_subsystem_device_id() {
         let device = LinuxPCIDevice::new(PLACEHOLDER_PCI_DEVICE);
-        assert_ne!(device.subsystem_device_id(), Vec::new());
+        assert!(!device.subsystem_device_id().is_empty());
     }
 
     #[test]
@@ -597,4 +635,259 @@ mod tests {
         let device = LinuxPCIDevice::new(PLACEHOLDER_PCI_DEVICE);
         assert_ne!(device.class_name(), "");
     }
+
+    #[test]
+    fn test_coprocessor_class_name() {
+        let sysfs = FakeSysfs::new("coprocessor-class-name");
+        sysfs.add_device("0000:00:02.0", &[("class", "0x400000")]);
+        sysfs.add_device("0000:00:03.0", &[("class", "0x2e0000")]);
+        let context = sysfs.context();
+
+        // The coprocessor class is 0x40, which 0x2e, its decimal spelling read as hex, mustn't match.
+        let coprocessor = LinuxPCIDevice::with_context("00:02.0", &context);
+        assert_eq!(
+            coprocessor.class_name(),
+            DeviceClass::Coprocessor.to_string()
+        );
+        let unknown = LinuxPCIDevice::with_context("00:03.0", &context);
+        assert_eq!(unknown.class_name(), DeviceClass::Unclassified.to_string());
+    }
+
+    #[test]
+    fn test_with_context() {
+        let sysfs = FakeSysfs::new("with-context");
+        sysfs.add_device("0000:00:02.0", &[("numa_node", "1")]);
+        let context = sysfs.context();
+
+        let device = LinuxPCIDevice::try_with_context("00:02.0", &context).unwrap();
+        assert_eq!(
+            device.path(),
+            context.pci_devices_path().join("0000:00:02.0")
+        );
+        assert_eq!(device.numa_node(), 1);
+        assert_eq!(device.vendor(), VendorId(0x8086));
+        assert_eq!(device.device(), DeviceId(0x1234));
+        assert_eq!(device.class_code(), ClassCode::new(0x03, 0x00, 0x00));
+        assert_eq!(device.revision_id(), Revision(0x01));
+        assert_eq!(device.subsystem().to_string(), "8086:0001");
+        assert_eq!(device.vendor_name(), "Intel Corporation");
+        assert_eq!(device.device_name(), "Fake Controller");
+        assert_eq!(device.subsystem_name(), "Fake Subsystem");
+        assert_eq!(device.subclass_name(), "VGA compatible controller");
+
+        let devices = LinuxPCIDevice::try_fetch_with_context(&context, None).unwrap();
+        assert_eq!(devices.len(), 1);
+    }
+
+    #[test]
+    fn test_non_zero_domain() {
+        let sysfs = FakeSysfs::new("non-zero-domain");
+        sysfs.add_device("0000:01:00.0", &[]);
+        sysfs.add_device("10000:01:00.0", &[("numa_node", "1")]);
+        let context = sysfs.context();
+
+        let device = LinuxPCIDevice::try_with_context("10000:01:00.0", &context).unwrap();
+        assert_eq!(device.numa_node(), 1);
+        assert_eq!(device.address(), "10000:01:00.0");
+        assert_eq!(device.pci_address().domain, 0x10000);
+
+        let device = LinuxPCIDevice::try_with_context("01:00.0", &context).unwrap();
+        assert_eq!(device.numa_node(), -1);
+        assert_eq!(device.address(), "01:00.0");
+    }
+
+    #[test]
+    fn test_prog_if() {
+        let sysfs = FakeSysfs::new("prog-if");
+        sysfs.add_device("0000:00:14.0", &[("class", "0x0c0330")]);
+        sysfs.add_device("0000:00:1a.0", &[("class", "0x0c0320")]);
+        let context = sysfs.context();
+
+        let xhci = LinuxPCIDevice::try_with_context("00:14.0", &context).unwrap();
+        assert_eq!(xhci.prog_if(), 0x30);
+        assert_eq!(xhci.subclass_name(), "USB controller");
+        assert_eq!(xhci.prog_if_name(), "XHCI");
+
+        let ehci = LinuxPCIDevice::try_with_context("00:1a.0", &context).unwrap();
+        assert_eq!(ehci.prog_if_name(), "EHCI");
+    }
+
+    #[test]
+    fn test_iter() {
+        let sysfs = FakeSysfs::new("iter");
+        sysfs.add_device("0000:00:02.0", &[]);
+        sysfs.add_device("0000:00:14.0", &[("class", "0x0c0330")]);
+        sysfs.add_device("0000:00:1f.0", &[("vendor", "0xzzzz")]);
+        let context = sysfs.context();
+
+        assert_eq!(LinuxPCIDevice::iter_with_context(&context).count(), 3);
+
+        let usb: Vec<LinuxPCIDevice> = LinuxPCIDevice::iter_with_context(&context)
+            .filter(|d| d.class_code().class == 0x0c)
+            .take(1)
+            .collect();
+        assert_eq!(usb.len(), 1);
+        assert_eq!(usb[0].address(), "00:14.0");
+
+        let results: Vec<Result<LinuxPCIDevice>> =
+            LinuxPCIDevice::try_iter_with_context(&context).collect();
+        assert_eq!(results.iter().filter(|r| r.is_err()).count(), 1);
+    }
+
+    #[test]
+    fn test_order() {
+        let sysfs = FakeSysfs::new("order");
+        sysfs.add_device("0001:00:00.0", &[]);
+        let root_port = sysfs.add_device("0000:00:1c.0", &[("class", "0x060400")]);
+        sysfs.add_device("0000:00:1f.3", &[]);
+        sysfs.add_device_behind(&root_port, "0000:02:00.0", &[]);
+        sysfs.add_device("0000:00:02.0", &[]);
+        let context = sysfs.context();
+        std::fs::create_dir(context.pci_devices_path().join("not-a-device")).unwrap();
+
+        let addresses = |order| -> Vec<String> {
+            let devices: Vec<LinuxPCIDevice> = LinuxPCIDevice::iter_with_context(&context)
+                .order(order)
+                .collect();
+            // The entry without an address comes last.
+            assert!(devices.last().unwrap().path().ends_with("not-a-device"));
+            devices[..devices.len() - 1]
+                .iter()
+                .map(|d| d.address())
+                .collect()
+        };
+        assert_eq!(
+            addresses(Order::Address),
+            ["00:02.0", "00:1c.0", "00:1f.3", "02:00.0", "0001:00:00.0"]
+        );
+        assert_eq!(
+            addresses(Order::Topology),
+            ["00:02.0", "00:1c.0", "02:00.0", "00:1f.3", "0001:00:00.0"]
+        );
+
+        let first = LinuxPCIDevice::fetch_with_context(&context, Some(1));
+        assert_eq!(first[0].address(), "00:02.0");
+    }
+
+    #[test]
+    fn test_config_space() {
+        let sysfs = FakeSysfs::new("config-space");
+        let path = sysfs.add_device("0000:00:14.0", &[]);
+        let context = sysfs.context();
+        let device = LinuxPCIDevice::with_context("00:14.0", &context);
+        assert!(matches!(device.config_space(), Err(Error::Io { .. })));
+
+        let mut data = crate::config_space::tests::endpoint();
+        data.truncate(64);
+        std::fs::write(path.join("config"), data).unwrap();
+        let config = device.config_space().unwrap();
+        assert!(config.is_truncated());
+        assert_eq!(config.vendor_id(), VendorId(0x8086));
+    }
+
+    #[test]
+    fn test_bars() {
+        let sysfs = FakeSysfs::new("bars");
+        let resource = "0x00000000f6000000 0x00000000f6ffffff 0x0000000000040200\n\
+                        0x0000000000000000 0x0000000000000000 0x0000000000000000";
+        let path = sysfs.add_device("0000:01:00.0", &[("resource", resource)]);
+        let device = LinuxPCIDevice::with_context("01:00.0", &sysfs.context());
+
+        let bars = device.bars().unwrap();
+        assert_eq!(bars.len(), 1);
+        assert_eq!((bars[0].index, bars[0].start), (0, 0xf600_0000));
+
+        sysfs.write(&path, "resource", "0x0 0x0");
+        assert!(matches!(
+            device.bars(),
+            Err(Error::MalformedAttribute { .. })
+        ));
+    }
+
+    #[test]
+    fn test_iter_missing_sysfs() {
+        let context = Context::builder().sysfs_root("/nonexistent").build();
+        assert_eq!(LinuxPCIDevice::iter_with_context(&context).count(), 0);
+
+        let mut devices = LinuxPCIDevice::try_iter_with_context(&context);
+        assert!(matches!(devices.next(), Some(Err(Error::Io { .. }))));
+        assert!(devices.next().is_none());
+    }
+
+    #[test]
+    fn test_limit() {
+        assert_eq!(limit(None), usize::MAX);
+        assert_eq!(limit(Some(0)), usize::MAX);
+        assert_eq!(limit(Some(3)), 3);
+    }
+
+    #[test]
+    fn test_try_with_context_malformed_attribute() {
+        let sysfs = FakeSysfs::new("malformed-attribute");
+        sysfs.add_device("0000:00:02.0", &[("vendor", "0xzzzz")]);
+
+        let error = LinuxPCIDevice::try_with_context("00:02.0", &sysfs.context()).unwrap_err();
+        assert!(matches!(error, Error::MalformedAttribute { .. }));
+    }
+
+    #[test]
+    fn test_try_new_not_found() {
+        let error = LinuxPCIDevice::try_new("ffff:ff:1f.7").unwrap_err();
+        assert!(matches!(error, Error::NotFound(_)));
+    }
+
+    #[test]
+    fn test_names_are_vendor_scoped() {
+        let ids: PciIds = "\
+10de  NVIDIA Corporation
+\t1234  GeForce 1234
+8086  Intel Corporation
+\t1234  Ethernet Controller 1234
+\t\t10de 1234  Not An NVIDIA Card
+"
+        .parse()
+        .unwrap();
+
+        let mut device = LinuxPCIDevice {
+            vendor_id: VendorId(0x8086),
+            device_id: DeviceId(0x1234),
+            subsystem_id: SubsystemId::new(VendorId(0x10de), DeviceId(0x1234)),
+            ..Default::default()
+        };
+        device.set_vendor_name(&ids);
+        device.set_device_name(&ids);
+        device.set_subsystem_name(&ids);
+
+        assert_eq!(device.vendor_name(), "Intel Corporation");
+        assert_eq!(device.device_name(), "Ethernet Controller 1234");
+        assert_eq!(device.subsystem_name(), "Not An NVIDIA Card");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let sysfs = FakeSysfs::new("serde");
+        sysfs.add_device("0000:00:02.0", &[]);
+        let device = LinuxPCIDevice::with_context("00:02.0", &sysfs.context());
+
+        let json = serde_json::to_value(&device).unwrap();
+        assert_eq!(json["address"], "0000:00:02.0");
+        assert_eq!(json["vendor_id"], "8086");
+        assert_eq!(json["class_id"], "030000");
+        assert_eq!(json["subsystem_id"], "8086:0001");
+        assert!(json.get("context").is_none());
+
+        let parsed: LinuxPCIDevice = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(parsed.pci_address(), device.pci_address());
+        assert_eq!(parsed.device_name(), "Fake Controller");
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), json);
+
+        let class = serde_json::to_string(&DeviceClass::DisplayController).unwrap();
+        assert_eq!(class, "\"DisplayController\"");
+        let class: DeviceClass = serde_json::from_str(&class).unwrap();
+        assert_eq!(
+            class.to_string(),
+            DeviceClass::DisplayController.to_string()
+        );
+    }
 }
@@ -0,0 +1,457 @@
+use super::LinuxPCIDevice;
+use crate::address::PciAddress;
+use crate::context::Context;
+use crate::ids::{ClassCode, DeviceId, SubsystemId, VendorId};
+use crate::private::Properties;
+use std::ops::{Bound, RangeBounds};
+
+/// A composable filter over PCI devices.
+///
+/// Every method adding a condition combines it with the existing ones through a logical AND,
+/// use [`Query::or()`] to match devices satisfying either of two queries.
+///
+/// When enumerating devices, the conditions are evaluated from the cheapest to the most expensive,
+/// and the information of a device is only fetched in full once it is known to match.
+///
+/// # Examples
+///
+/// ```
+/// use aparato::{Query, VendorId};
+///
+/// // All enabled Mellanox network controllers sitting on NUMA node 1.
+/// let nics = Query::new()
+///     .vendor(VendorId(0x15b3))
+///     .class(0x02)
+///     .numa_node(1)
+///     .enabled(true)
+///     .fetch();
+///
+/// // All the GPUs, whether they're made by AMD or NVIDIA.
+/// let gpus = Query::new()
+///     .class(0x03)
+///     .and(Query::new().vendor(VendorId(0x1002)).or(Query::new().vendor(VendorId(0x10de))))
+///     .fetch();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query {
+    predicate: Predicate,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Predicate {
+    Address(Bound<PciAddress>, Bound<PciAddress>),
+    Vendor(VendorId),
+    Device(DeviceId),
+    Class(u8),
+    Subclass(u8, u8),
+    ClassCode(ClassCode),
+    SubsystemVendor(VendorId),
+    Subsystem(SubsystemId),
+    Driver(Option<String>),
+    NumaNode(isize),
+    Enabled(bool),
+    And(Vec<Predicate>),
+    Or(Vec<Predicate>),
+}
+
+impl Query {
+    /// This function returns a query which matches every device.
+    pub fn new() -> Self {
+        Query {
+            predicate: Predicate::And(Vec::new()),
+        }
+    }
+
+    /// This function restricts the query to devices made by the given vendor.
+    pub fn vendor(self, vendor: VendorId) -> Self {
+        self.with(Predicate::Vendor(vendor))
+    }
+
+    /// This function restricts the query to devices with the given device ID.
+    pub fn device(self, device: DeviceId) -> Self {
+        self.with(Predicate::Device(device))
+    }
+
+    /// This function restricts the query to devices of the given class, e.g. `0x02` for network controllers.
+    pub fn class(self, class: u8) -> Self {
+        self.with(Predicate::Class(class))
+    }
+
+    /// This function restricts the query to devices of the given class and subclass.
+    pub fn subclass(self, class: u8, subclass: u8) -> Self {
+        self.with(Predicate::Subclass(class, subclass))
+    }
+
+    /// This function restricts the query to devices of the given class, subclass and programming interface.
+    pub fn class_code(self, class_code: ClassCode) -> Self {
+        self.with(Predicate::ClassCode(class_code))
+    }
+
+    /// This function restricts the query to devices whose subsystem is made by the given vendor.
+    pub fn subsystem_vendor(self, vendor: VendorId) -> Self {
+        self.with(Predicate::SubsystemVendor(vendor))
+    }
+
+    /// This function restricts the query to devices with the given subsystem.
+    pub fn subsystem(self, subsystem: SubsystemId) -> Self {
+        self.with(Predicate::Subsystem(subsystem))
+    }
+
+    /// This function restricts the query to devices bound to the given driver, e.g. `vfio-pci`.
+    pub fn driver(self, driver: &str) -> Self {
+        self.with(Predicate::Driver(Some(driver.to_owned())))
+    }
+
+    /// This function restricts the query to devices which aren't bound to any driver.
+    pub fn unbound(self) -> Self {
+        self.with(Predicate::Driver(None))
+    }
+
+    /// This function restricts the query to devices attached to the given NUMA node.
+    pub fn numa_node(self, node: isize) -> Self {
+        self.with(Predicate::NumaNode(node))
+    }
+
+    /// This function restricts the query to devices which are, or aren't, enabled.
+    pub fn enabled(self, enabled: bool) -> Self {
+        self.with(Predicate::Enabled(enabled))
+    }
+
+    /// This function restricts the query to devices whose address falls within `range`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use aparato::{PciAddress, Query};
+    ///
+    /// let start: PciAddress = "0000:80:00.0".parse().unwrap();
+    /// let second_socket = Query::new().address_range(start..);
+    /// ```
+    pub fn address_range<R: RangeBounds<PciAddress>>(self, range: R) -> Self {
+        self.with(Predicate::Address(
+            range.start_bound().cloned(),
+            range.end_bound().cloned(),
+        ))
+    }
+
+    /// This function returns a query matching the devices which match both `self` and `other`.
+    pub fn and(self, other: Query) -> Self {
+        self.with(other.predicate)
+    }
+
+    /// This function returns a query matching the devices which match either `self` or `other`.
+    pub fn or(self, other: Query) -> Self {
+        let mut alternatives = match self.predicate {
+            Predicate::Or(alternatives) => alternatives,
+            predicate => vec![predicate],
+        };
+        alternatives.push(other.predicate);
+        sort_by_cost(&mut alternatives);
+
+        Query {
+            predicate: Predicate::Or(alternatives),
+        }
+    }
+
+    /// This function returns whether an already fetched device matches the query.
+    pub fn matches(&self, device: &LinuxPCIDevice) -> bool {
+        let mut probe = Probe::fetched(device.clone());
+        probe.eval(&self.predicate)
+    }
+
+    /// This function returns a lazy iterator over the PCI devices matching the query.
+    pub fn iter(&self) -> impl Iterator<Item = LinuxPCIDevice> + '_ {
+        self.iter_with_context(Context::global())
+    }
+
+    /// This function behaves like [`Query::iter()`], except that sysfs and the pci.ids database
+    /// are looked for at the locations given by `context`.
+    pub fn iter_with_context(
+        &self,
+        context: &Context,
+    ) -> impl Iterator<Item = LinuxPCIDevice> + '_ {
+        LinuxPCIDevice::iter_with_context(context)
+            .blanks()
+            .filter_map(move |device| {
+                let mut probe = Probe::blank(device);
+                if !probe.eval(&self.predicate) {
+                    return None;
+                }
+
+                let mut device = probe.device;
+                let _ = device.populate(false);
+                Some(device)
+            })
+    }
+
+    /// This function returns a **list** of the PCI devices matching the query.
+    pub fn fetch(&self) -> Vec<LinuxPCIDevice> {
+        self.iter().collect()
+    }
+
+    /// This function behaves like [`Query::fetch()`], except that sysfs and the pci.ids database
+    /// are looked for at the locations given by `context`.
+    pub fn fetch_with_context(&self, context: &Context) -> Vec<LinuxPCIDevice> {
+        self.iter_with_context(context).collect()
+    }
+
+    fn with(self, predicate: Predicate) -> Self {
+        let mut conditions = match self.predicate {
+            Predicate::And(conditions) => conditions,
+            predicate => vec![predicate],
+        };
+        match predicate {
+            Predicate::And(others) => conditions.extend(others),
+            predicate => conditions.push(predicate),
+        }
+        sort_by_cost(&mut conditions);
+
+        Query {
+            predicate: Predicate::And(conditions),
+        }
+    }
+}
+
+impl Default for Query {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Predicate {
+    /// This function returns the number of sysfs reads needed to evaluate the predicate, at worst.
+    fn cost(&self) -> usize {
+        match self {
+            // The address is part of the path of the device.
+            Predicate::Address(..) => 0,
+            Predicate::Subsystem(_) => 2,
+            Predicate::And(predicates) | Predicate::Or(predicates) => {
+                predicates.iter().map(Predicate::cost).sum()
+            }
+            _ => 1,
+        }
+    }
+}
+
+fn sort_by_cost(predicates: &mut [Predicate]) {
+    predicates.sort_by_key(Predicate::cost);
+}
+
+/// The attributes a `Probe` can read.
+#[derive(Clone, Copy, PartialEq)]
+enum Attribute {
+    Address,
+    Vendor,
+    Device,
+    Class,
+    SubsystemVendor,
+    SubsystemDevice,
+    NumaNode,
+    Enabled,
+}
+
+/// A device whose attributes are only read once a predicate needs them.
+struct Probe {
+    device: LinuxPCIDevice,
+    loaded: Vec<Attribute>,
+    failed: Vec<Attribute>,
+    driver: Option<Option<String>>,
+}
+
+impl Probe {
+    fn blank(device: LinuxPCIDevice) -> Self {
+        Probe {
+            device,
+            loaded: Vec::new(),
+            failed: Vec::new(),
+            driver: None,
+        }
+    }
+
+    fn fetched(device: LinuxPCIDevice) -> Self {
+        Probe {
+            device,
+            loaded: vec![
+                Attribute::Address,
+                Attribute::Vendor,
+                Attribute::Device,
+                Attribute::Class,
+                Attribute::SubsystemVendor,
+                Attribute::SubsystemDevice,
+                Attribute::NumaNode,
+                Attribute::Enabled,
+            ],
+            failed: Vec::new(),
+            driver: None,
+        }
+    }
+
+    /// This function reads the given attribute unless it was already, and returns whether it could be read.
+    fn load(&mut self, attribute: Attribute) -> bool {
+        if self.loaded.contains(&attribute) {
+            return true;
+        }
+        if self.failed.contains(&attribute) {
+            return false;
+        }
+
+        let device = &mut self.device;
+        let result = match attribute {
+            Attribute::Address => device.set_address(),
+            Attribute::Vendor => device.set_vendor_id(),
+            Attribute::Device => device.set_device_id(),
+            Attribute::Class => device.set_class_id(),
+            Attribute::SubsystemVendor => device.set_subsystem_vendor_id(),
+            Attribute::SubsystemDevice => device.set_subsystem_device_id(),
+            Attribute::NumaNode => device.set_numa_node(),
+            Attribute::Enabled => device.set_enabled(),
+        };
+
+        match result {
+            Ok(()) => self.loaded.push(attribute),
+            Err(_) => self.failed.push(attribute),
+        }
+        result.is_ok()
+    }
+
+    fn eval(&mut self, predicate: &Predicate) -> bool {
+        match predicate {
+            Predicate::Address(start, end) => {
+                self.load(Attribute::Address) && (*start, *end).contains(&self.device.address)
+            }
+            Predicate::Vendor(vendor) => {
+                self.load(Attribute::Vendor) && self.device.vendor_id == *vendor
+            }
+            Predicate::Device(device) => {
+                self.load(Attribute::Device) && self.device.device_id == *device
+            }
+            Predicate::Class(class) => {
+                self.load(Attribute::Class) && self.device.class_id.class == *class
+            }
+            Predicate::Subclass(class, subclass) => {
+                self.load(Attribute::Class)
+                    && self.device.class_id.class == *class
+                    && self.device.class_id.subclass == *subclass
+            }
+            Predicate::ClassCode(class_code) => {
+                self.load(Attribute::Class) && self.device.class_id == *class_code
+            }
+            Predicate::SubsystemVendor(vendor) => {
+                self.load(Attribute::SubsystemVendor) && self.device.subsystem_id.vendor == *vendor
+            }
+            Predicate::Subsystem(subsystem) => {
+                self.load(Attribute::SubsystemVendor)
+                    && self.load(Attribute::SubsystemDevice)
+                    && self.device.subsystem_id == *subsystem
+            }
+            Predicate::Driver(driver) => {
+                let device = &self.device;
+                self.driver.get_or_insert_with(|| device.driver()) == driver
+            }
+            Predicate::NumaNode(node) => {
+                self.load(Attribute::NumaNode) && self.device.numa_node == *node
+            }
+            Predicate::Enabled(enabled) => {
+                self.load(Attribute::Enabled) && self.device.enabled == *enabled
+            }
+            Predicate::And(predicates) => predicates.iter().all(|p| self.eval(p)),
+            Predicate::Or(predicates) => predicates.iter().any(|p| self.eval(p)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fake_sysfs::FakeSysfs;
+    use super::*;
+    use crate::Device;
+
+    fn addresses(devices: Vec<LinuxPCIDevice>) -> Vec<String> {
+        let mut addresses: Vec<String> = devices.iter().map(|d| d.address()).collect();
+        addresses.sort();
+        addresses
+    }
+
+    #[test]
+    fn test_query() {
+        let sysfs = FakeSysfs::new("query");
+        sysfs.add_device("0000:00:02.0", &[]);
+        let nic = sysfs.add_device(
+            "0000:81:00.0",
+            &[
+                ("vendor", "0x15b3"),
+                ("class", "0x020000"),
+                ("numa_node", "1"),
+            ],
+        );
+        sysfs.add_device(
+            "0000:81:00.1",
+            &[
+                ("vendor", "0x15b3"),
+                ("class", "0x020000"),
+                ("numa_node", "1"),
+                ("enable", "0"),
+            ],
+        );
+        sysfs.add_device(
+            "0000:01:00.0",
+            &[
+                ("vendor", "0x15b3"),
+                ("class", "0x020000"),
+                ("numa_node", "0"),
+            ],
+        );
+        sysfs.bind_driver(&nic, "mlx5_core");
+        let context = sysfs.context();
+
+        let query = Query::new()
+            .vendor(VendorId(0x15b3))
+            .class(0x02)
+            .numa_node(1)
+            .enabled(true);
+        assert_eq!(addresses(query.fetch_with_context(&context)), ["81:00.0"]);
+
+        let query = Query::new().driver("mlx5_core");
+        assert_eq!(addresses(query.fetch_with_context(&context)), ["81:00.0"]);
+
+        let query = Query::new().class(0x03).or(Query::new().numa_node(0));
+        assert_eq!(
+            addresses(query.fetch_with_context(&context)),
+            ["00:02.0", "01:00.0"]
+        );
+
+        let start: PciAddress = "0000:80:00.0".parse().unwrap();
+        let query = Query::new().address_range(start..).unbound();
+        assert_eq!(addresses(query.fetch_with_context(&context)), ["81:00.1"]);
+    }
+
+    #[test]
+    fn test_cheapest_first() {
+        let query = Query::new()
+            .subsystem(SubsystemId::default())
+            .vendor(VendorId(0x8086))
+            .address_range(..);
+        match query.predicate {
+            Predicate::And(predicates) => {
+                let costs: Vec<usize> = predicates.iter().map(Predicate::cost).collect();
+                assert_eq!(costs, [0, 1, 2]);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_matches() {
+        let sysfs = FakeSysfs::new("query-matches");
+        sysfs.add_device("0000:00:14.0", &[("class", "0x0c0330")]);
+        let device = LinuxPCIDevice::with_context("00:14.0", &sysfs.context());
+
+        assert!(Query::new()
+            .class_code(ClassCode::new(0x0c, 0x03, 0x30))
+            .matches(&device));
+        assert!(!Query::new()
+            .subclass(0x0c, 0x03)
+            .enabled(false)
+            .matches(&device));
+    }
+}
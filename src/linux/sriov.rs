@@ -0,0 +1,186 @@
+use super::{LinuxPCIDevice, SysfsWrite};
+use crate::error::{Error, Result};
+use crate::Device;
+
+/// The SR-IOV state of a physical function, returned by [`LinuxPCIDevice::sriov()`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Sriov {
+    /// The number of virtual functions the device supports.
+    pub total_vfs: u16,
+    /// The number of virtual functions currently enabled.
+    pub num_vfs: u16,
+    /// The routing ID offset of the first virtual function, relative to the physical function.
+    pub offset: u16,
+    /// The routing ID distance between consecutive virtual functions.
+    pub stride: u16,
+}
+
+impl LinuxPCIDevice {
+    /// This function returns the SR-IOV state of the device, or `None` if the device isn't a physical function.
+    pub fn sriov(&self) -> Result<Option<Sriov>> {
+        if self.read_optional_attribute("sriov_totalvfs")?.is_none() {
+            return Ok(None);
+        }
+
+        Ok(Some(Sriov {
+            total_vfs: self.read_parsed_attribute("sriov_totalvfs")?,
+            num_vfs: self.read_parsed_attribute("sriov_numvfs")?,
+            offset: self.read_parsed_attribute("sriov_offset")?,
+            stride: self.read_parsed_attribute("sriov_stride")?,
+        }))
+    }
+
+    /// This function returns the enabled virtual functions of the device, in the order of their index.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use aparato::{Device, PCIDevice};
+    ///
+    /// let nic = PCIDevice::new("3b:00.0");
+    /// for vf in nic.virtual_functions().unwrap() {
+    ///     println!("{} -> {}", nic.address(), vf.address());
+    /// }
+    /// ```
+    pub fn virtual_functions(&self) -> Result<Vec<LinuxPCIDevice>> {
+        let entries = std::fs::read_dir(&self.path).map_err(|e| Error::io(&self.path, e))?;
+
+        let mut functions = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|e| Error::io(&self.path, e))?;
+            let index = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_prefix("virtfn"))
+                .and_then(|index| index.parse::<u16>().ok());
+            if let Some(index) = index {
+                let path =
+                    std::fs::canonicalize(entry.path()).map_err(|e| Error::io(entry.path(), e))?;
+                functions.push((index, path));
+            }
+        }
+        functions.sort_by_key(|(index, _)| *index);
+
+        Ok(functions
+            .into_iter()
+            .map(|(_, path)| LinuxPCIDevice::with_context(&path.to_string_lossy(), &self.context))
+            .collect())
+    }
+
+    /// This function returns the physical function the device belongs to, or `None` if the device isn't a virtual function.
+    pub fn physical_function(&self) -> Option<LinuxPCIDevice> {
+        let path = std::fs::canonicalize(self.path.join("physfn")).ok()?;
+        Some(LinuxPCIDevice::with_context(
+            &path.to_string_lossy(),
+            &self.context,
+        ))
+    }
+
+    /// This function returns whether the device is a virtual function.
+    pub fn is_virtual_function(&self) -> bool {
+        self.path.join("physfn").exists()
+    }
+
+    /// This function enables `count` virtual functions, `0` disabling all of them.
+    ///
+    /// The kernel refuses to change the number of enabled virtual functions unless none are,
+    /// they are thus disabled first when needed, which is reflected by the returned writes.
+    /// No write is performed if `count` virtual functions are already enabled.
+    ///
+    /// [`Error::TooManyVfs`] is returned if the device supports fewer than `count` virtual functions.
+    pub fn set_num_vfs(&self, count: u16) -> Result<Vec<SysfsWrite>> {
+        let total: u16 = self.read_parsed_attribute("sriov_totalvfs")?;
+        if count > total {
+            return Err(Error::TooManyVfs {
+                path: self.path(),
+                requested: count,
+                total,
+            });
+        }
+
+        let current: u16 = self.read_parsed_attribute("sriov_numvfs")?;
+        let path = self.path.join("sriov_numvfs");
+        let mut writes = Vec::new();
+        if current != count {
+            if current != 0 && count != 0 {
+                writes.push(self.write_sysfs(path.clone(), "0".to_owned())?);
+            }
+            writes.push(self.write_sysfs(path, count.to_string())?);
+        }
+        Ok(writes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fake_sysfs::FakeSysfs;
+    use super::*;
+
+    #[test]
+    fn test_sriov() {
+        let sysfs = FakeSysfs::new("sriov");
+        let pf = sysfs.add_device(
+            "0000:3b:00.0",
+            &[
+                ("sriov_totalvfs", "64"),
+                ("sriov_numvfs", "2"),
+                ("sriov_offset", "16"),
+                ("sriov_stride", "1"),
+            ],
+        );
+        let usb = sysfs.add_device("0000:00:14.0", &[]);
+        // The indices are compared as numbers, virtfn10 following virtfn2.
+        sysfs.add_virtual_function(&pf, 10, "0000:3b:0b.2");
+        sysfs.add_virtual_function(&pf, 2, "0000:3b:02.2");
+        let context = sysfs.context();
+
+        let pf = LinuxPCIDevice::with_context("3b:00.0", &context);
+        assert_eq!(
+            pf.sriov().unwrap(),
+            Some(Sriov {
+                total_vfs: 64,
+                num_vfs: 2,
+                offset: 16,
+                stride: 1,
+            })
+        );
+        assert!(!pf.is_virtual_function());
+        assert!(pf.physical_function().is_none());
+
+        let vfs = pf.virtual_functions().unwrap();
+        let addresses: Vec<String> = vfs.iter().map(|vf| vf.address()).collect();
+        assert_eq!(addresses, ["3b:02.2", "3b:0b.2"]);
+        assert!(vfs[0].is_virtual_function());
+        assert_eq!(vfs[0].physical_function().unwrap().address(), "3b:00.0");
+        assert_eq!(vfs[0].sriov().unwrap(), None);
+
+        let usb = LinuxPCIDevice::with_context(&usb.to_string_lossy(), &context);
+        assert_eq!(usb.sriov().unwrap(), None);
+        assert!(usb.virtual_functions().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_set_num_vfs() {
+        let sysfs = FakeSysfs::new("set-num-vfs");
+        sysfs.add_device(
+            "0000:3b:00.0",
+            &[("sriov_totalvfs", "8"), ("sriov_numvfs", "0")],
+        );
+        let pf = LinuxPCIDevice::with_context("3b:00.0", &sysfs.context());
+        let values = |writes: Vec<SysfsWrite>| -> Vec<String> {
+            writes.into_iter().map(|write| write.value).collect()
+        };
+
+        assert_eq!(values(pf.set_num_vfs(4).unwrap()), ["4"]);
+        assert!(values(pf.set_num_vfs(4).unwrap()).is_empty());
+        assert_eq!(values(pf.set_num_vfs(8).unwrap()), ["0", "8"]);
+        assert!(matches!(
+            pf.set_num_vfs(9),
+            Err(Error::TooManyVfs {
+                requested: 9,
+                total: 8,
+                ..
+            })
+        ));
+    }
+}
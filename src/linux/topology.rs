@@ -0,0 +1,502 @@
+use super::LinuxPCIDevice;
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::Device;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// The PCI devices of the system, arranged as a tree following the bridges they sit behind.
+///
+/// # Examples
+///
+/// ```no_run
+/// use aparato::Topology;
+///
+/// let topology = Topology::new().unwrap();
+///
+/// // Prints the same tree as `lspci -tv`.
+/// print!("{}", topology.render(true));
+///
+/// for &root in topology.roots() {
+///     let node = topology.node(root);
+///     println!("{:?} with {} children", node.kind, node.children.len());
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Topology {
+    nodes: Vec<TopologyNode>,
+    roots: Vec<NodeId>,
+}
+
+/// The handle of a node of a [Topology].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(usize);
+
+/// A device of a [Topology], along with its position in the tree.
+#[derive(Debug, Clone)]
+pub struct TopologyNode {
+    pub device: LinuxPCIDevice,
+    pub kind: NodeKind,
+    /// The bus right behind the bridge.
+    pub secondary_bus: Option<u8>,
+    /// The highest bus behind the bridge.
+    pub subordinate_bus: Option<u8>,
+    /// The bridge the device sits behind, or `None` if the device sits on a root bus.
+    pub parent: Option<NodeId>,
+    /// The devices sitting behind the bridge, ordered by address.
+    pub children: Vec<NodeId>,
+}
+
+/// The role of a device in a [Topology].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeKind {
+    /// A bridge sitting on a root bus, e.g. a PCI Express root port.
+    RootPort,
+    /// A bridge sitting behind another bridge, e.g. a port of a PCI Express switch.
+    Bridge,
+    /// A device which doesn't lead to other buses.
+    Endpoint,
+}
+
+impl Topology {
+    /// This function returns the topology of the PCI devices of the system.
+    pub fn new() -> Result<Topology> {
+        Self::with_context(Context::global())
+    }
+
+    /// This function behaves like [`Topology::new()`], except that sysfs and the pci.ids database
+    /// are looked for at the locations given by `context`.
+    pub fn with_context(context: &Context) -> Result<Topology> {
+        let mut nodes = Vec::new();
+        let mut paths = HashMap::new();
+        for device in LinuxPCIDevice::iter_with_context(context) {
+            let path =
+                std::fs::canonicalize(&device.path).map_err(|e| Error::io(&device.path, e))?;
+            paths.insert(path, NodeId(nodes.len()));
+            nodes.push(TopologyNode {
+                secondary_bus: bus_number(&device, "secondary_bus_number")?,
+                subordinate_bus: bus_number(&device, "subordinate_bus_number")?,
+                device,
+                kind: NodeKind::Endpoint,
+                parent: None,
+                children: Vec::new(),
+            });
+        }
+
+        // The hierarchy of sysfs is trusted first, the bus numbers of the bridges are only used
+        // for the devices it places neither behind a bridge nor right below a host bridge.
+        let mut parents: Vec<Option<NodeId>> = vec![None; nodes.len()];
+        for (path, &id) in &paths {
+            let directory = path.parent();
+            parents[id.0] = match directory.and_then(|directory| paths.get(directory)) {
+                Some(&parent) => Some(parent),
+                None if directory.is_some_and(is_host_bridge) => None,
+                None => bridge_for(&nodes, id),
+            };
+        }
+
+        let mut topology = Topology {
+            nodes,
+            roots: Vec::new(),
+        };
+        for (index, parent) in parents.into_iter().enumerate() {
+            topology.nodes[index].parent = parent;
+            match parent {
+                Some(parent) => topology.nodes[parent.0].children.push(NodeId(index)),
+                None => topology.roots.push(NodeId(index)),
+            }
+        }
+
+        for node in &mut topology.nodes {
+            if node.secondary_bus.is_some() || !node.children.is_empty() {
+                node.kind = match node.parent {
+                    Some(_) => NodeKind::Bridge,
+                    None => NodeKind::RootPort,
+                };
+            }
+        }
+
+        Ok(topology)
+    }
+
+    /// This function returns the devices sitting on the root buses, ordered by address.
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    /// This function returns the node of the given handle.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the handle belongs to another `Topology`.
+    pub fn node(&self, id: NodeId) -> &TopologyNode {
+        &self.nodes[id.0]
+    }
+
+    /// This function returns every node of the topology, ordered by address.
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &TopologyNode)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| (NodeId(index), node))
+    }
+
+    /// This function returns the node of the device with the given address, e.g. `01:00.0`.
+    pub fn find(&self, address: &str) -> Option<NodeId> {
+        let address = address.parse().ok()?;
+        self.iter()
+            .find(|(_, node)| node.device.pci_address() == address)
+            .map(|(id, _)| id)
+    }
+
+    /// This function returns the number of devices.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// This function returns whether there are no devices.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// This function renders the topology the same way `lspci -t` does,
+    /// or `lspci -tv` if `verbose` is set, in which case the names of the endpoints are displayed.
+    pub fn render(&self, verbose: bool) -> String {
+        let mut renderer = Renderer {
+            topology: self,
+            verbose,
+            line: String::new(),
+            output: String::new(),
+        };
+        renderer.bridge(None, 0);
+        renderer.output
+    }
+
+    /// This function groups the children of `parent`, or the root devices, by the bus they sit on.
+    fn buses(&self, parent: Option<NodeId>) -> Vec<(u32, u8, Vec<NodeId>)> {
+        let children = match parent {
+            Some(parent) => &self.node(parent).children,
+            None => &self.roots,
+        };
+
+        let mut buses: Vec<(u32, u8, Vec<NodeId>)> = Vec::new();
+        for &child in children {
+            let address = self.node(child).device.pci_address();
+            match buses
+                .iter_mut()
+                .find(|(domain, bus, _)| (*domain, *bus) == (address.domain, address.bus))
+            {
+                Some((_, _, devices)) => devices.push(child),
+                None => buses.push((address.domain, address.bus, vec![child])),
+            }
+        }
+        buses.sort_by_key(|(domain, bus, _)| (*domain, *bus));
+        buses
+    }
+}
+
+impl TopologyNode {
+    /// This function returns whether the device leads to other buses.
+    pub fn is_bridge(&self) -> bool {
+        self.kind != NodeKind::Endpoint
+    }
+}
+
+/// This function reads a bus number attribute of a bridge, which other devices lack.
+fn bus_number(device: &LinuxPCIDevice, name: &str) -> Result<Option<u8>> {
+    match device.read_optional_attribute(name)? {
+        Some(value) => match value.parse() {
+            Ok(bus) => Ok(Some(bus)),
+            Err(_) => Err(device.malformed_attribute(name, value)),
+        },
+        None => Ok(None),
+    }
+}
+
+/// This function returns whether `path` is the sysfs directory of a host bridge, e.g. `pci0000:00`.
+fn is_host_bridge(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let domain_bus = name
+        .strip_prefix("pci")
+        .and_then(|rest| rest.split_once(':'));
+    matches!(domain_bus, Some((domain, bus))
+        if u32::from_str_radix(domain, 16).is_ok() && u8::from_str_radix(bus, 16).is_ok())
+}
+
+/// This function returns the bridge whose bus range holds the bus of `node`, the narrowest range winning.
+///
+/// Only bridges sitting on a lower bus are considered, so that a bridge whose bus range isn't assigned,
+/// and thus reads as `00`, doesn't become its own parent or the parent of the devices of bus `00`.
+fn bridge_for(nodes: &[TopologyNode], node: NodeId) -> Option<NodeId> {
+    let address = nodes[node.0].device.pci_address();
+    nodes
+        .iter()
+        .enumerate()
+        .filter(|&(index, _)| index != node.0)
+        .filter(|(_, bridge)| {
+            let bridge = bridge.device.pci_address();
+            bridge.domain == address.domain && bridge.bus < address.bus
+        })
+        .filter_map(|(index, bridge)| {
+            let range = bridge.secondary_bus?..=bridge.subordinate_bus?;
+            Some((index, range)).filter(|(_, range)| range.contains(&address.bus))
+        })
+        .min_by_key(|(_, range)| range.end() - range.start())
+        .map(|(index, _)| NodeId(index))
+}
+
+/// This struct renders a [Topology] the way lspci does, see its ls-tree.c.
+///
+/// The line being rendered is kept once printed, with its branches turned into `|`
+/// and everything else into spaces, so that it becomes the indentation of the following lines.
+struct Renderer<'a> {
+    topology: &'a Topology,
+    verbose: bool,
+    line: String,
+    output: String,
+}
+
+impl Renderer<'_> {
+    /// This function writes `text` at `position`, and returns the position following it.
+    fn write(&mut self, position: usize, text: &str) -> usize {
+        self.line.truncate(position);
+        self.line.push_str(text);
+        self.line.len()
+    }
+
+    fn print(&mut self, position: usize) {
+        self.line.truncate(position);
+        self.output.push_str(&self.line);
+        self.output.push('\n');
+        self.line = self
+            .line
+            .chars()
+            .map(|c| if c == '+' || c == '|' { '|' } else { ' ' })
+            .collect();
+    }
+
+    /// This function renders the buses behind `bridge`, or the root buses if it's `None`.
+    fn bridge(&mut self, bridge: Option<NodeId>, position: usize) {
+        let position = self.write(position, "-");
+        let mut buses = self.topology.buses(bridge);
+
+        if buses.len() <= 1 {
+            let (domain, bus, devices) = buses.pop().unwrap_or_default();
+            let position = match bridge {
+                None => self.write(position, &format!("[{:04x}:{:02x}]-", domain, bus)),
+                Some(_) => position,
+            };
+            self.bus(&devices, position);
+        } else {
+            let last = buses.len() - 1;
+            for (index, (domain, bus, devices)) in buses.into_iter().enumerate() {
+                let branch = if index == last { '\\' } else { '+' };
+                let label = format!("{}-[{:04x}:{:02x}]-", branch, domain, bus);
+                let next = self.write(position, &label);
+                self.bus(&devices, next);
+            }
+        }
+    }
+
+    fn bus(&mut self, devices: &[NodeId], position: usize) {
+        match devices {
+            [] => self.print(position),
+            [device] => {
+                let next = self.write(position, "--");
+                self.device(*device, next);
+            }
+            [others @ .., last] => {
+                for &device in others {
+                    let next = self.write(position, "+-");
+                    self.device(device, next);
+                }
+                let next = self.write(position, "\\-");
+                self.device(*last, next);
+            }
+        }
+    }
+
+    fn device(&mut self, id: NodeId, position: usize) {
+        let node = self.topology.node(id);
+        let address = node.device.pci_address();
+        let mut position = self.write(
+            position,
+            &format!("{:02x}.{:x}", address.device, address.function),
+        );
+
+        if node.is_bridge() {
+            let (secondary, subordinate) = self.bus_range(node);
+            let range = if secondary == subordinate {
+                format!("-[{:02x}]-", secondary)
+            } else {
+                format!("-[{:02x}-{:02x}]-", secondary, subordinate)
+            };
+            position = self.write(position, &range);
+            self.bridge(Some(id), position);
+            return;
+        }
+
+        if self.verbose {
+            position = self.write(position, &format!("  {}", name(&node.device)));
+        }
+        self.print(position);
+    }
+
+    /// This function returns the buses behind the bridge, which are guessed from its children
+    /// when sysfs doesn't provide them.
+    fn bus_range(&self, node: &TopologyNode) -> (u8, u8) {
+        let buses = node
+            .children
+            .iter()
+            .map(|&child| self.topology.node(child).device.pci_address().bus);
+        let secondary = node.secondary_bus.or_else(|| buses.clone().min());
+        let subordinate = node.subordinate_bus.or_else(|| buses.max());
+        (
+            secondary.unwrap_or_default(),
+            subordinate.unwrap_or_default(),
+        )
+    }
+}
+
+/// This function returns the vendor and device names of the device, the same way lspci does when one is unknown.
+fn name(device: &LinuxPCIDevice) -> String {
+    match (device.vendor_name().as_str(), device.device_name().as_str()) {
+        ("", _) => format!("Device {}:{}", device.vendor(), device.device()),
+        (vendor, "") => format!("{} Device {}", vendor, device.device()),
+        (vendor, name) => format!("{} {}", vendor, name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fake_sysfs::FakeSysfs;
+    use super::*;
+
+    fn bridge(
+        secondary: &'static str,
+        subordinate: &'static str,
+    ) -> [(&'static str, &'static str); 3] {
+        [
+            ("class", "0x060400"),
+            ("secondary_bus_number", secondary),
+            ("subordinate_bus_number", subordinate),
+        ]
+    }
+
+    fn fake_topology(sysfs: &FakeSysfs) -> Topology {
+        sysfs.add_device("0000:00:00.0", &[("class", "0x060000")]);
+        let root_port = sysfs.add_device("0000:00:01.0", &bridge("1", "2"));
+        let switch = sysfs.add_device_behind(&root_port, "0000:01:00.0", &bridge("2", "2"));
+        sysfs.add_device_behind(&switch, "0000:02:00.0", &[]);
+        sysfs.add_device_behind(&switch, "0000:02:00.1", &[("vendor", "0x10de")]);
+        sysfs.add_device("0000:00:1c.0", &bridge("3", "3"));
+        sysfs.add_device("0000:00:1d.0", &bridge("4", "4"));
+        sysfs.add_device("0000:00:1f.0", &[]);
+        // The device isn't nested in the directory of any bridge, its bus number places it.
+        let virtual_devices = root_port.parent().unwrap().with_file_name("virtual");
+        sysfs.add_device_behind(&virtual_devices, "0000:03:00.0", &[]);
+        Topology::with_context(&sysfs.context()).unwrap()
+    }
+
+    #[test]
+    fn test_topology() {
+        let sysfs = FakeSysfs::new("topology");
+        let topology = fake_topology(&sysfs);
+        assert_eq!(topology.len(), 9);
+
+        let roots: Vec<String> = topology
+            .roots()
+            .iter()
+            .map(|&id| topology.node(id).device.address())
+            .collect();
+        assert_eq!(
+            roots,
+            ["00:00.0", "00:01.0", "00:1c.0", "00:1d.0", "00:1f.0"]
+        );
+
+        let root_port = topology.find("00:01.0").unwrap();
+        assert_eq!(topology.node(root_port).kind, NodeKind::RootPort);
+        let switch = topology.find("01:00.0").unwrap();
+        assert_eq!(topology.node(switch).kind, NodeKind::Bridge);
+        assert_eq!(topology.node(switch).parent, Some(root_port));
+        assert_eq!(topology.node(switch).children.len(), 2);
+
+        let endpoint = topology.find("03:00.0").unwrap();
+        assert_eq!(topology.node(endpoint).kind, NodeKind::Endpoint);
+        assert_eq!(topology.node(endpoint).parent, topology.find("00:1c.0"));
+        assert_eq!(
+            topology.node(topology.find("00:00.0").unwrap()).kind,
+            NodeKind::Endpoint
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let sysfs = FakeSysfs::new("topology-render");
+        let topology = fake_topology(&sysfs);
+
+        assert_eq!(
+            topology.render(false),
+            "\
+-[0000:00]-+-00.0
+           +-01.0-[01-02]----00.0-[02]--+-00.0
+           |                            \\-00.1
+           +-1c.0-[03]----00.0
+           +-1d.0-[04]--
+           \\-1f.0
+"
+        );
+
+        let verbose = topology.render(true);
+        let lines: Vec<&str> = verbose.lines().collect();
+        assert_eq!(
+            lines[0],
+            "-[0000:00]-+-00.0  Intel Corporation Fake Controller"
+        );
+        assert_eq!(
+            lines[2],
+            "           |                            \\-00.1  Device 10de:1234"
+        );
+    }
+
+    #[test]
+    fn test_unassigned_bus_range() {
+        let sysfs = FakeSysfs::new("topology-unassigned-bus-range");
+        let host_bridge = sysfs.add_device("0000:00:00.0", &[]);
+        // Neither device is nested in a host bridge, so their bus numbers are looked at,
+        // which mustn't make the bridge the parent of itself or of its neighbour.
+        let virtual_devices = host_bridge.parent().unwrap().with_file_name("virtual");
+        sysfs.add_device_behind(&virtual_devices, "0000:00:1c.0", &bridge("0", "0"));
+        sysfs.add_device_behind(&virtual_devices, "0000:00:1f.0", &[]);
+        let topology = Topology::with_context(&sysfs.context()).unwrap();
+
+        assert_eq!(topology.roots().len(), 3);
+        assert!(topology.iter().all(|(_, node)| node.parent.is_none()));
+        assert_eq!(
+            topology.render(false),
+            "\
+-[0000:00]-+-00.0
+           +-1c.0-[00]--
+           \\-1f.0
+"
+        );
+    }
+
+    #[test]
+    fn test_render_several_root_buses() {
+        let sysfs = FakeSysfs::new("topology-root-buses");
+        sysfs.add_device("0000:00:00.0", &[]);
+        sysfs.add_device("0000:80:00.0", &[]);
+        sysfs.add_device("0000:80:01.0", &[]);
+        let topology = Topology::with_context(&sysfs.context()).unwrap();
+
+        assert_eq!(
+            topology.render(false),
+            "\
+-+-[0000:00]---00.0
+ \\-[0000:80]-+-00.0
+             \\-01.0
+"
+        );
+    }
+}
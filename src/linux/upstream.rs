@@ -0,0 +1,108 @@
+use super::LinuxPCIDevice;
+use crate::error::{Error, Result};
+use crate::Device;
+use std::path::PathBuf;
+
+/// The bridges between a device and the host bridge it hangs off, returned by [`LinuxPCIDevice::upstream_path()`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use aparato::{Device, PCIDevice};
+///
+/// let first = PCIDevice::new("41:00.0").upstream_path().unwrap();
+/// let second = PCIDevice::new("42:00.0").upstream_path().unwrap();
+///
+/// if first.host_bridge != second.host_bridge {
+///     println!("The GPUs hang off different root complexes");
+/// } else if let Some(bridge) = first.common_bridge(&second) {
+///     println!("The GPUs share the bridge {}", bridge.address());
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct UpstreamPath {
+    /// The bridges the device sits behind, the closest coming first and the root port last.
+    pub bridges: Vec<LinuxPCIDevice>,
+    /// The sysfs directory of the host bridge, e.g. `/sys/devices/pci0000:00`.
+    pub host_bridge: PathBuf,
+}
+
+impl UpstreamPath {
+    /// This function returns the root port, or `None` if the device sits on a root bus.
+    pub fn root_port(&self) -> Option<&LinuxPCIDevice> {
+        self.bridges.last()
+    }
+
+    /// This function returns the closest bridge both devices sit behind, e.g. the port of a switch they share,
+    /// or `None` if they only meet at the host bridge, if at all.
+    pub fn common_bridge(&self, other: &UpstreamPath) -> Option<&LinuxPCIDevice> {
+        self.bridges.iter().find(|bridge| {
+            other
+                .bridges
+                .iter()
+                .any(|other| other.pci_address() == bridge.pci_address())
+        })
+    }
+}
+
+impl LinuxPCIDevice {
+    /// This function returns the bridges between the device and the host bridge it hangs off.
+    pub fn upstream_path(&self) -> Result<UpstreamPath> {
+        let mut path = std::fs::canonicalize(&self.path).map_err(|e| Error::io(&self.path, e))?;
+        let mut bridges = Vec::new();
+
+        while let Some(parent) = LinuxPCIDevice::blank(&self.context, path.clone()).parent_path() {
+            bridges.push(LinuxPCIDevice::with_context(
+                &parent.to_string_lossy(),
+                &self.context,
+            ));
+            path = parent;
+        }
+
+        Ok(UpstreamPath {
+            bridges,
+            host_bridge: path.parent().unwrap_or(&path).to_path_buf(),
+        })
+    }
+
+    /// This function returns the root port the device hangs off, or `None` if the device sits on a root bus.
+    pub fn root_port(&self) -> Result<Option<LinuxPCIDevice>> {
+        Ok(self.upstream_path()?.bridges.pop())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fake_sysfs::FakeSysfs;
+    use super::*;
+
+    #[test]
+    fn test_upstream_path() {
+        let sysfs = FakeSysfs::new("upstream-path");
+        let root_port = sysfs.add_device("0000:00:01.0", &[]);
+        let upstream = sysfs.add_device_behind(&root_port, "0000:01:00.0", &[]);
+        let first = sysfs.add_device_behind(&upstream, "0000:02:00.0", &[]);
+        let second = sysfs.add_device_behind(&upstream, "0000:02:01.0", &[]);
+        sysfs.add_device_behind(&first, "0000:03:00.0", &[]);
+        sysfs.add_device_behind(&second, "0000:04:00.0", &[]);
+        sysfs.add_device("0000:80:00.0", &[]);
+        let context = sysfs.context();
+
+        let gpu = LinuxPCIDevice::with_context("03:00.0", &context);
+        let path = gpu.upstream_path().unwrap();
+        let bridges: Vec<String> = path.bridges.iter().map(|b| b.address()).collect();
+        assert_eq!(bridges, ["02:00.0", "01:00.0", "00:01.0"]);
+        assert!(path.host_bridge.ends_with("pci0000:00"));
+        assert_eq!(gpu.root_port().unwrap().unwrap().address(), "00:01.0");
+
+        let other = LinuxPCIDevice::with_context("04:00.0", &context);
+        let other = other.upstream_path().unwrap();
+        assert_eq!(path.common_bridge(&other).unwrap().address(), "01:00.0");
+
+        let far = LinuxPCIDevice::with_context("80:00.0", &context);
+        let far = far.upstream_path().unwrap();
+        assert!(far.bridges.is_empty() && far.root_port().is_none());
+        assert!(far.host_bridge.ends_with("pci0000:80"));
+        assert!(path.common_bridge(&far).is_none());
+    }
+}
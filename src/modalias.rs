@@ -0,0 +1,317 @@
+//! This module contains [Modalias], the identifiers the kernel matches drivers against,
+//! and [ModuleAliases], which finds the kernel modules able to drive a device.
+
+use crate::error::{Error, Result};
+use crate::ids::{ClassCode, DeviceId, SubsystemId, VendorId};
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::str::FromStr;
+
+/// The modalias of a PCI device, e.g. `pci:v00008086d0000A3EDsv000017AAsd00002292bc0Csc03i30`.
+///
+/// # Examples
+///
+/// ```
+/// use aparato::modalias::Modalias;
+///
+/// let modalias: Modalias = "pci:v00008086d0000A3EDsv000017AAsd00002292bc0Csc03i30"
+///     .parse()
+///     .unwrap();
+///
+/// assert_eq!(modalias.vendor.to_string(), "8086");
+/// assert_eq!(modalias.class_code.to_string(), "0c0330");
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Modalias {
+    pub vendor: VendorId,
+    pub device: DeviceId,
+    pub subsystem: SubsystemId,
+    pub class_code: ClassCode,
+}
+
+/// The PCI aliases declared by the kernel modules, as listed in `/lib/modules/$(uname -r)/modules.alias`.
+///
+/// # Examples
+///
+/// ```
+/// use aparato::modalias::{Modalias, ModuleAliases};
+///
+/// let aliases: ModuleAliases = "\
+/// alias pci:v00008086d0000A3EDsv*sd*bc*sc*i* xhci_pci
+/// alias pci:v*d*sv*sd*bc0Csc03i30* xhci_pci
+/// alias pci:v000010DEd*sv*sd*bc03sc*i* nouveau
+/// "
+/// .parse()
+/// .unwrap();
+///
+/// let modalias: Modalias = "pci:v00008086d0000A3EDsv000017AAsd00002292bc0Csc03i30"
+///     .parse()
+///     .unwrap();
+/// assert_eq!(aliases.modules(&modalias), ["xhci_pci"]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ModuleAliases {
+    /// The patterns along with the module they belong to.
+    aliases: Vec<(String, String)>,
+}
+
+impl ModuleAliases {
+    /// This function reads and parses the modules.alias file located at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| Error::io(path, e))?;
+        Self::parse(BufReader::new(file)).map_err(|e| match e {
+            Error::Io { source, .. } => Error::io(path, source),
+            e => e,
+        })
+    }
+
+    /// This function parses a modules.alias file from the given reader.
+    ///
+    /// Aliases of other buses than PCI, and lines which aren't aliases, are ignored.
+    pub fn parse<R: BufRead>(reader: R) -> Result<Self> {
+        let mut aliases = Vec::new();
+        for line in reader.lines() {
+            let line = line.map_err(|e| Error::io("modules.alias", e))?;
+            let mut fields = line.split_whitespace();
+            if let (Some("alias"), Some(pattern), Some(module)) =
+                (fields.next(), fields.next(), fields.next())
+            {
+                if pattern.starts_with("pci:") {
+                    aliases.push((pattern.to_owned(), module.to_owned()));
+                }
+            }
+        }
+        Ok(ModuleAliases { aliases })
+    }
+
+    /// This function returns the modules having an alias which matches `modalias`, in the order of the file.
+    pub fn modules(&self, modalias: &Modalias) -> Vec<&str> {
+        let modalias = modalias.to_string();
+        let mut modules: Vec<&str> = Vec::new();
+        for (pattern, module) in &self.aliases {
+            if !modules.contains(&module.as_str())
+                && glob_match(pattern.as_bytes(), modalias.as_bytes())
+            {
+                modules.push(module);
+            }
+        }
+        modules
+    }
+
+    /// This function returns the number of PCI aliases.
+    pub fn len(&self) -> usize {
+        self.aliases.len()
+    }
+
+    /// This function returns whether there are no PCI aliases.
+    pub fn is_empty(&self) -> bool {
+        self.aliases.is_empty()
+    }
+}
+
+impl FromStr for ModuleAliases {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s.as_bytes())
+    }
+}
+
+impl fmt::Display for Modalias {
+    /// This function displays the modalias the way the kernel does, with uppercase hexadecimal digits.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "pci:v{:08X}d{:08X}sv{:08X}sd{:08X}bc{:02X}sc{:02X}i{:02X}",
+            self.vendor.0,
+            self.device.0,
+            self.subsystem.vendor.0,
+            self.subsystem.device.0,
+            self.class_code.class,
+            self.class_code.subclass,
+            self.class_code.prog_if
+        )
+    }
+}
+
+impl FromStr for Modalias {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::InvalidModalias(s.to_owned());
+        let mut rest = s.trim().strip_prefix("pci:").ok_or_else(invalid)?;
+        let mut id = |prefix| take_field(&mut rest, prefix, 8).and_then(|v| u16::try_from(v).ok());
+        let vendor = id("v").ok_or_else(invalid)?;
+        let device = id("d").ok_or_else(invalid)?;
+        let subsystem_vendor = id("sv").ok_or_else(invalid)?;
+        let subsystem_device = id("sd").ok_or_else(invalid)?;
+        let mut byte = |prefix| take_field(&mut rest, prefix, 2).map(|v| v as u8);
+        let class = byte("bc").ok_or_else(invalid)?;
+        let subclass = byte("sc").ok_or_else(invalid)?;
+        let prog_if = byte("i").ok_or_else(invalid)?;
+
+        if !rest.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(Modalias {
+            vendor: VendorId(vendor),
+            device: DeviceId(device),
+            subsystem: SubsystemId::new(VendorId(subsystem_vendor), DeviceId(subsystem_device)),
+            class_code: ClassCode::new(class, subclass, prog_if),
+        })
+    }
+}
+
+/// This function removes a field made of `prefix` and `digits` hexadecimal digits from the start of `rest`,
+/// and returns its value.
+fn take_field(rest: &mut &str, prefix: &str, digits: usize) -> Option<u32> {
+    let value = rest.strip_prefix(prefix)?;
+    let digits = value
+        .get(..digits)
+        .filter(|d| d.bytes().all(|b| b.is_ascii_hexdigit()))?;
+    *rest = &value[digits.len()..];
+    u32::from_str_radix(digits, 16).ok()
+}
+
+/// This function matches `text` against a shell wildcard pattern, the same way `fnmatch(3)` does,
+/// which is what the kernel's module loader uses for aliases.
+///
+/// `*` matches any sequence, `?` matches any character and `[...]` matches a set of characters,
+/// which may contain ranges and be negated with `!` or `^`.
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // The position following the last `*`, along with the text it has consumed so far.
+    let mut star = None;
+
+    while t < text.len() {
+        if pattern.get(p) == Some(&b'*') {
+            p += 1;
+            star = Some((p, t));
+        } else if let Some(len) = match_char(&pattern[p..], text[t]) {
+            p += len;
+            t += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Let the last `*` consume one more character, and try again.
+            p = star_p;
+            t = star_t + 1;
+            star = Some((star_p, t));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&b| b == b'*')
+}
+
+/// This function returns the length of the element at the start of `pattern`, if it matches `c`.
+fn match_char(pattern: &[u8], c: u8) -> Option<usize> {
+    match pattern.first()? {
+        b'?' => Some(1),
+        b'[' => match match_set(pattern, c) {
+            Some((true, len)) => Some(len),
+            Some((false, _)) => None,
+            // A `[` which isn't closed is matched literally.
+            None if c == b'[' => Some(1),
+            None => None,
+        },
+        b'\\' if pattern.len() > 1 => Some(2).filter(|_| pattern[1] == c),
+        &b => Some(1).filter(|_| b == c),
+    }
+}
+
+/// This function returns whether the set at the start of `pattern` matches `c`, along with the length of the set,
+/// or `None` if the set isn't closed.
+fn match_set(pattern: &[u8], c: u8) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negated = matches!(pattern.get(i), Some(b'!') | Some(b'^'));
+    if negated {
+        i += 1;
+    }
+
+    let start = i;
+    let mut matched = false;
+    loop {
+        let first = *pattern.get(i)?;
+        // A `]` right after the opening bracket is part of the set.
+        if first == b']' && i > start {
+            return Some((matched != negated, i + 1));
+        }
+
+        match (pattern.get(i + 1), pattern.get(i + 2)) {
+            (Some(b'-'), Some(&last)) if last != b']' => {
+                matched |= (first..=last).contains(&c);
+                i += 3;
+            }
+            _ => {
+                matched |= first == c;
+                i += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODALIAS: &str = "pci:v000010DEd00002684sv00001043sd000088E2bc03sc00i00";
+
+    #[test]
+    fn test_parse_modalias() {
+        let modalias: Modalias = MODALIAS.parse().unwrap();
+        assert_eq!(modalias.vendor, VendorId(0x10de));
+        assert_eq!(modalias.device, DeviceId(0x2684));
+        assert_eq!(modalias.subsystem.to_string(), "1043:88e2");
+        assert_eq!(modalias.class_code, ClassCode::new(0x03, 0x00, 0x00));
+        assert_eq!(modalias.to_string(), MODALIAS);
+
+        assert!("usb:v1D6Bp0002".parse::<Modalias>().is_err());
+        assert!("pci:v000010DEd00002684".parse::<Modalias>().is_err());
+        assert!("pci:v000010DEd00002684sv00001043sd000088E2bc03sc00i00x"
+            .parse::<Modalias>()
+            .is_err());
+        assert!("pci:v000110DEd00002684sv00001043sd000088E2bc03sc00i00"
+            .parse::<Modalias>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_glob_match() {
+        let matches = |pattern: &str, text: &str| glob_match(pattern.as_bytes(), text.as_bytes());
+        assert!(matches("pci:v*d*", "pci:v000010DEd00002684"));
+        assert!(matches("*", ""));
+        assert!(matches("a?c", "abc"));
+        assert!(!matches("a?c", "ac"));
+        assert!(matches("bc0[2-3]", "bc03"));
+        assert!(!matches("bc0[!2-3]", "bc03"));
+        assert!(matches("bc0[^45]", "bc03"));
+        assert!(matches("[]x]", "]"));
+        assert!(matches("a[b", "a[b"));
+        assert!(matches("*sd*bc03*", "sv1sd2bc03i00"));
+        assert!(!matches("*bc03", "bc030"));
+    }
+
+    #[test]
+    fn test_modules() {
+        let aliases: ModuleAliases = "\
+# Aliases extracted from modules themselves.
+alias usb:v*p*d*dc*dsc*dp*ic09isc*ip*in* usbcore
+alias pci:v000010DEd*sv*sd*bc03sc0[02]i00* nouveau
+alias pci:v*d*sv*sd*bc03sc00i00* vgacon
+alias pci:v000010DEd00002684sv*sd*bc*sc*i* nvidia
+alias pci:v000010DEd*sv*sd*bc03sc00i00* nouveau
+alias pci:v00008086d*sv*sd*bc03sc*i* i915
+"
+        .parse()
+        .unwrap();
+        assert_eq!(aliases.len(), 5);
+
+        let modalias = MODALIAS.parse().unwrap();
+        assert_eq!(aliases.modules(&modalias), ["nouveau", "vgacon", "nvidia"]);
+    }
+}
@@ -0,0 +1,551 @@
+//! This module contains [PciIds], an indexed copy of the [pci.ids](https://pci-ids.ucw.cz/) database.
+//!
+//! The database is parsed once and kept in hashed lookup tables, which is considerably
+//! faster than scanning the file every time a name needs to be resolved.
+
+use crate::error::{Error, Result};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::str::FromStr;
+
+/// A parsed and indexed copy of the pci.ids database.
+///
+/// # Examples
+///
+/// ```
+/// use aparato::pci_ids::PciIds;
+///
+/// let ids: PciIds = "8086  Intel Corporation\n\t1237  440FX - 82441FX PMC [Natoma]\n"
+///     .parse()
+///     .unwrap();
+///
+/// assert_eq!(ids.vendor_name(0x8086), Some("Intel Corporation"));
+/// assert_eq!(ids.device_name(0x8086, 0x1237), Some("440FX - 82441FX PMC [Natoma]"));
+/// ```
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PciIds {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_string::hex_keys"))]
+    vendors: HashMap<u16, Vendor>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_string::hex_keys"))]
+    classes: HashMap<u8, Class>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    warnings: Vec<Error>,
+}
+
+/// A vendor entry of the pci.ids database.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Vendor {
+    name: String,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_string::hex_keys"))]
+    devices: HashMap<u16, DeviceEntry>,
+}
+
+/// A device entry of the pci.ids database, which belongs to a [Vendor].
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceEntry {
+    name: String,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_string::hex_keys"))]
+    subsystems: HashMap<(u16, u16), String>,
+}
+
+/// A class entry of the pci.ids database.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Class {
+    name: String,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_string::hex_keys"))]
+    subclasses: HashMap<u8, Subclass>,
+}
+
+/// A subclass entry of the pci.ids database, which belongs to a [Class].
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Subclass {
+    name: String,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_string::hex_keys"))]
+    prog_ifs: HashMap<u8, String>,
+}
+
+impl PciIds {
+    /// This function reads and parses the pci.ids file located at `path`.
+    ///
+    /// [`Error::PciIdsMissing`] is returned if there is no such file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => Error::PciIdsMissing(vec![path.to_owned()]),
+            _ => Error::io(path, e),
+        })?;
+        Self::parse(BufReader::new(file))
+    }
+
+    /// This function parses a pci.ids database from the given reader.
+    ///
+    /// Entries that can't be parsed, e.g. because of a local patch gone wrong, are skipped along with the entries
+    /// nested in them, and reported through [`PciIds::warnings()`]. Only failing to read the database is an error.
+    pub fn parse<R: BufRead>(reader: R) -> Result<Self> {
+        let mut ids = PciIds::default();
+        let mut parser = Parser::default();
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line.map_err(|e| Error::InvalidPciIds {
+                line: index + 1,
+                reason: e.to_string(),
+            })?;
+            if let Err(e) = parser.parse_line(&mut ids, index + 1, &line) {
+                parser.skip_block(&line);
+                ids.warnings.push(e);
+            }
+        }
+
+        Ok(ids)
+    }
+
+    /// This function returns the errors met on the entries which were skipped while parsing the database,
+    /// as [`Error::InvalidPciIds`].
+    pub fn warnings(&self) -> &[Error] {
+        &self.warnings
+    }
+
+    /// This function returns the vendor entry of the given vendor ID.
+    pub fn vendor(&self, vendor: u16) -> Option<&Vendor> {
+        self.vendors.get(&vendor)
+    }
+
+    /// This function returns the class entry of the given class ID.
+    pub fn class(&self, class: u8) -> Option<&Class> {
+        self.classes.get(&class)
+    }
+
+    /// This function returns the name of the given vendor.
+    pub fn vendor_name(&self, vendor: u16) -> Option<&str> {
+        self.vendor(vendor).map(Vendor::name)
+    }
+
+    /// This function returns the name of the given device of the given vendor.
+    ///
+    /// Only the devices listed in the block of `vendor` are considered.
+    pub fn device_name(&self, vendor: u16, device: u16) -> Option<&str> {
+        self.vendor(vendor)?.device(device).map(DeviceEntry::name)
+    }
+
+    /// This function returns the name of the given subsystem of the given device.
+    ///
+    /// Only the subsystems listed under `device` in the block of `vendor` are considered.
+    pub fn subsystem_name(
+        &self,
+        vendor: u16,
+        device: u16,
+        subsystem_vendor: u16,
+        subsystem_device: u16,
+    ) -> Option<&str> {
+        self.vendor(vendor)?
+            .device(device)?
+            .subsystem_name(subsystem_vendor, subsystem_device)
+    }
+
+    /// This function returns the name of the given class.
+    pub fn class_name(&self, class: u8) -> Option<&str> {
+        self.class(class).map(Class::name)
+    }
+
+    /// This function returns the name of the given subclass of the given class.
+    pub fn subclass_name(&self, class: u8, subclass: u8) -> Option<&str> {
+        self.class(class)?.subclass(subclass).map(Subclass::name)
+    }
+
+    /// This function returns the name of the given programming interface of the given subclass.
+    pub fn prog_if_name(&self, class: u8, subclass: u8, prog_if: u8) -> Option<&str> {
+        self.class(class)?.subclass(subclass)?.prog_if_name(prog_if)
+    }
+}
+
+impl FromStr for PciIds {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s.as_bytes())
+    }
+}
+
+impl Vendor {
+    /// This function returns the name of the vendor.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// This function returns the device entry of the given device ID.
+    pub fn device(&self, device: u16) -> Option<&DeviceEntry> {
+        self.devices.get(&device)
+    }
+}
+
+impl DeviceEntry {
+    /// This function returns the name of the device.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// This function returns the name of the given subsystem.
+    pub fn subsystem_name(&self, subsystem_vendor: u16, subsystem_device: u16) -> Option<&str> {
+        self.subsystems
+            .get(&(subsystem_vendor, subsystem_device))
+            .map(String::as_str)
+    }
+}
+
+impl Class {
+    /// This function returns the name of the class.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// This function returns the subclass entry of the given subclass ID.
+    pub fn subclass(&self, subclass: u8) -> Option<&Subclass> {
+        self.subclasses.get(&subclass)
+    }
+}
+
+impl Subclass {
+    /// This function returns the name of the subclass.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// This function returns the name of the given programming interface.
+    pub fn prog_if_name(&self, prog_if: u8) -> Option<&str> {
+        self.prog_ifs.get(&prog_if).map(String::as_str)
+    }
+}
+
+/// The block of the database the parser is currently in.
+#[derive(Default)]
+enum Section {
+    #[default]
+    None,
+    Vendor(u16, Option<u16>),
+    Class(u8, Option<u8>),
+}
+
+#[derive(Default)]
+struct Parser {
+    section: Section,
+    /// The depth of the malformed entry whose nested entries are being skipped.
+    skipped_depth: Option<usize>,
+}
+
+impl Parser {
+    /// This function leaves the block of the malformed entry held by `line`.
+    fn skip_block(&mut self, line: &str) {
+        self.skipped_depth = Some(line.len() - line.trim_start_matches('\t').len());
+    }
+
+    fn parse_line(&mut self, ids: &mut PciIds, number: usize, line: &str) -> Result<()> {
+        if line.trim().is_empty() || line.starts_with('#') {
+            return Ok(());
+        }
+
+        let depth = line.len() - line.trim_start_matches('\t').len();
+        let content = &line[depth..];
+        let malformed = || invalid_line(number, line);
+
+        // The entries nested in a malformed one would otherwise be attached to the entry preceding it.
+        match self.skipped_depth {
+            Some(skipped) if depth > skipped => return Ok(()),
+            _ => self.skipped_depth = None,
+        }
+
+        match (depth, &mut self.section) {
+            (0, section) => {
+                if let Some(class) = content.strip_prefix("C ") {
+                    let (id, name) = split_id(class.trim_start()).ok_or_else(malformed)?;
+                    let id = parse_hex::<u8>(id, 2).ok_or_else(malformed)?;
+                    ids.classes.entry(id).or_default().name = name.to_owned();
+                    *section = Section::Class(id, None);
+                } else if content.starts_with(|c: char| c.is_ascii_hexdigit()) {
+                    let (id, name) = split_id(content).ok_or_else(malformed)?;
+                    let id = parse_hex::<u16>(id, 4).ok_or_else(malformed)?;
+                    ids.vendors.entry(id).or_default().name = name.to_owned();
+                    *section = Section::Vendor(id, None);
+                } else {
+                    // Other top-level lists, such as the one holding the language codes,
+                    // are of no use to us, their entries are ignored until a known block begins.
+                    *section = Section::None;
+                }
+            }
+            (1, Section::Vendor(vendor, device)) => {
+                let (id, name) = split_id(content).ok_or_else(malformed)?;
+                let id = parse_hex::<u16>(id, 4).ok_or_else(malformed)?;
+                let vendor = ids.vendors.entry(*vendor).or_default();
+                vendor.devices.entry(id).or_default().name = name.to_owned();
+                *device = Some(id);
+            }
+            (2, Section::Vendor(vendor, Some(device))) => {
+                let (subsystem_vendor, rest) = split_id(content).ok_or_else(malformed)?;
+                let (subsystem_device, name) = split_id(rest).ok_or_else(malformed)?;
+                let subsystem_vendor =
+                    parse_hex::<u16>(subsystem_vendor, 4).ok_or_else(malformed)?;
+                let subsystem_device =
+                    parse_hex::<u16>(subsystem_device, 4).ok_or_else(malformed)?;
+                ids.vendors
+                    .entry(*vendor)
+                    .or_default()
+                    .devices
+                    .entry(*device)
+                    .or_default()
+                    .subsystems
+                    .insert((subsystem_vendor, subsystem_device), name.to_owned());
+            }
+            (1, Section::Class(class, subclass)) => {
+                let (id, name) = split_id(content).ok_or_else(malformed)?;
+                let id = parse_hex::<u8>(id, 2).ok_or_else(malformed)?;
+                let class = ids.classes.entry(*class).or_default();
+                class.subclasses.entry(id).or_default().name = name.to_owned();
+                *subclass = Some(id);
+            }
+            (2, Section::Class(class, Some(subclass))) => {
+                let (id, name) = split_id(content).ok_or_else(malformed)?;
+                let id = parse_hex::<u8>(id, 2).ok_or_else(malformed)?;
+                ids.classes
+                    .entry(*class)
+                    .or_default()
+                    .subclasses
+                    .entry(*subclass)
+                    .or_default()
+                    .prog_ifs
+                    .insert(id, name.to_owned());
+            }
+            (_, Section::None) => {}
+            _ => return Err(malformed()),
+        }
+
+        Ok(())
+    }
+}
+
+/// This function splits a line into its leading ID field and the remainder of the line.
+fn split_id(line: &str) -> Option<(&str, &str)> {
+    let end = line.find(char::is_whitespace)?;
+    let (id, rest) = line.split_at(end);
+    Some((id, rest.trim()))
+}
+
+/// This function parses a hexadecimal ID field which must be exactly `width` digits long.
+fn parse_hex<T: TryFrom<u32>>(field: &str, width: usize) -> Option<T> {
+    if field.len() != width || !field.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    u32::from_str_radix(field, 16)
+        .ok()
+        .and_then(|v| T::try_from(v).ok())
+}
+
+fn invalid_line(number: usize, line: &str) -> Error {
+    Error::InvalidPciIds {
+        line: number,
+        reason: format!("malformed entry {:?}", line),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = "\
+# pci.ids fixture
+#
+8086  Intel Corporation
+\t1237  440FX - 82441FX PMC [Natoma]
+\t7000  82371SB PIIX3 ISA [Natoma/Triton II]
+\t\t1af4 1100  Qemu virtual machine
+10de  NVIDIA Corporation
+\t1f91  TU117M [GeForce GTX 1650 Mobile / Max-Q]
+
+C 01  Mass storage controller
+\t08  Non-Volatile memory controller
+\t\t02  NVM Express
+C 0c  Serial bus controller
+\t03  USB controller
+\t\t00  UHCI
+\t\t30  XHCI
+";
+
+    #[test]
+    fn test_vendor_name() {
+        let ids: PciIds = FIXTURE.parse().unwrap();
+        assert_eq!(ids.vendor_name(0x8086), Some("Intel Corporation"));
+        assert_eq!(ids.vendor_name(0x10de), Some("NVIDIA Corporation"));
+        assert_eq!(ids.vendor_name(0x1af4), None);
+    }
+
+    #[test]
+    fn test_device_name() {
+        let ids: PciIds = FIXTURE.parse().unwrap();
+        assert_eq!(
+            ids.device_name(0x10de, 0x1f91),
+            Some("TU117M [GeForce GTX 1650 Mobile / Max-Q]")
+        );
+        assert_eq!(ids.device_name(0x8086, 0x1f91), None);
+    }
+
+    #[test]
+    fn test_subsystem_name() {
+        let ids: PciIds = FIXTURE.parse().unwrap();
+        assert_eq!(
+            ids.subsystem_name(0x8086, 0x7000, 0x1af4, 0x1100),
+            Some("Qemu virtual machine")
+        );
+    }
+
+    #[test]
+    fn test_class_names() {
+        let ids: PciIds = FIXTURE.parse().unwrap();
+        assert_eq!(ids.class_name(0x0c), Some("Serial bus controller"));
+        assert_eq!(ids.subclass_name(0x0c, 0x03), Some("USB controller"));
+        assert_eq!(ids.prog_if_name(0x0c, 0x03, 0x30), Some("XHCI"));
+        assert_eq!(ids.prog_if_name(0x01, 0x08, 0x02), Some("NVM Express"));
+    }
+
+    #[test]
+    fn test_open_missing() {
+        let error = PciIds::open("/nonexistent/pci.ids").unwrap_err();
+        assert!(matches!(error, Error::PciIdsMissing(_)));
+    }
+
+    #[test]
+    fn test_malformed_line() {
+        let ids = "8086  Intel Corporation\n\tzzzz  Broken\n"
+            .parse::<PciIds>()
+            .unwrap();
+        assert!(matches!(
+            ids.warnings(),
+            [Error::InvalidPciIds { line: 2, .. }]
+        ));
+        assert_eq!(ids.vendor_name(0x8086), Some("Intel Corporation"));
+    }
+
+    #[test]
+    fn test_malformed_line_is_skipped() {
+        // A locally patched database, with a broken device line in the middle of FIXTURE.
+        let patched = FIXTURE.replace(
+            "\t7000  82371SB PIIX3 ISA [Natoma/Triton II]\n",
+            "\t70O0  82371SB PIIX3 ISA [Natoma/Triton II]\n",
+        );
+        let ids: PciIds = patched.parse().unwrap();
+        assert!(matches!(
+            ids.warnings(),
+            [Error::InvalidPciIds { line: 5, .. }]
+        ));
+
+        // Everything else is kept, while the subsystem of the broken device isn't given to the previous one.
+        assert_eq!(
+            ids.device_name(0x8086, 0x1237),
+            Some("440FX - 82441FX PMC [Natoma]")
+        );
+        assert_eq!(ids.subsystem_name(0x8086, 0x1237, 0x1af4, 0x1100), None);
+        assert_eq!(ids.vendor_name(0x10de), Some("NVIDIA Corporation"));
+        assert_eq!(ids.prog_if_name(0x0c, 0x03, 0x30), Some("XHCI"));
+
+        let ids: PciIds = FIXTURE.parse().unwrap();
+        assert!(ids.warnings().is_empty());
+    }
+
+    const COLLIDING_FIXTURE: &str = "\
+1002  Advanced Micro Devices, Inc. [AMD/ATI]
+\t1234  Radeon HD 1234
+\t\t1028 5678  Radeon Subsystem
+1028  Dell
+\t5678  PERC 1234 Controller
+\t\t1002 1234  PERC Subsystem
+8086  Intel Corporation
+\t1234  Model 5678 Adapter
+\t\t1028 5678  Intel Subsystem
+";
+
+    #[test]
+    fn test_device_name_is_vendor_scoped() {
+        let ids: PciIds = COLLIDING_FIXTURE.parse().unwrap();
+        assert_eq!(ids.device_name(0x1002, 0x1234), Some("Radeon HD 1234"));
+        assert_eq!(ids.device_name(0x8086, 0x1234), Some("Model 5678 Adapter"));
+        assert_eq!(ids.device_name(0x1028, 0x1234), None);
+        assert_eq!(ids.device_name(0x8086, 0x5678), None);
+    }
+
+    #[test]
+    fn test_subsystem_name_is_device_scoped() {
+        let ids: PciIds = COLLIDING_FIXTURE.parse().unwrap();
+        assert_eq!(
+            ids.subsystem_name(0x1002, 0x1234, 0x1028, 0x5678),
+            Some("Radeon Subsystem")
+        );
+        assert_eq!(
+            ids.subsystem_name(0x8086, 0x1234, 0x1028, 0x5678),
+            Some("Intel Subsystem")
+        );
+        assert_eq!(
+            ids.subsystem_name(0x1028, 0x5678, 0x1002, 0x1234),
+            Some("PERC Subsystem")
+        );
+        // The subsystem IDs are swapped, which must not match.
+        assert_eq!(ids.subsystem_name(0x1002, 0x1234, 0x5678, 0x1028), None);
+        // The subsystem belongs to a device of another vendor.
+        assert_eq!(ids.subsystem_name(0x1028, 0x1234, 0x1028, 0x5678), None);
+    }
+
+    #[test]
+    fn test_ids_are_matched_exactly() {
+        // Matching is done on whole fields, a five digit ID is not a four digit one.
+        let bogus = format!("{}\t\t1028 56780  Bogus Subsystem\n", COLLIDING_FIXTURE);
+        let bogus: PciIds = bogus.parse().unwrap();
+        assert_eq!(bogus.warnings().len(), 1);
+        assert_eq!(
+            bogus.subsystem_name(0x8086, 0x1234, 0x1028, 0x5678),
+            Some("Intel Subsystem")
+        );
+
+        let ids: PciIds = COLLIDING_FIXTURE.parse().unwrap();
+        // IDs appearing inside names are left untouched and never matched.
+        assert_eq!(
+            ids.device_name(0x1028, 0x5678),
+            Some("PERC 1234 Controller")
+        );
+        assert_eq!(ids.vendor_name(0x5678), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let ids: PciIds = FIXTURE.parse().unwrap();
+        let json = serde_json::to_value(&ids).unwrap();
+        assert_eq!(
+            json["vendors"]["8086"]["devices"]["7000"]["subsystems"]["1af4:1100"],
+            "Qemu virtual machine"
+        );
+        assert_eq!(
+            json["classes"]["0c"]["subclasses"]["03"]["prog_ifs"]["30"],
+            "XHCI"
+        );
+
+        let parsed: PciIds = serde_json::from_value(json).unwrap();
+        assert_eq!(
+            parsed.subsystem_name(0x8086, 0x7000, 0x1af4, 0x1100),
+            Some("Qemu virtual machine")
+        );
+        assert_eq!(parsed.prog_if_name(0x01, 0x08, 0x02), Some("NVM Express"));
+
+        let bogus = r#"{"vendors": {"80866": {"name": "", "devices": {}}}, "classes": {}}"#;
+        assert!(serde_json::from_str::<PciIds>(bogus).is_err());
+        // The sign which integer parsing accepts isn't a hexadecimal digit.
+        let signed = r#"{"vendors": {}, "classes": {"+c": {"name": "", "subclasses": {}}}}"#;
+        assert!(serde_json::from_str::<PciIds>(signed).is_err());
+        let signed = r#"{"vendors": {"+086": {"name": "", "devices": {}}}, "classes": {}}"#;
+        assert!(serde_json::from_str::<PciIds>(signed).is_err());
+    }
+}
@@ -0,0 +1,95 @@
+//! This module lets types be serialized as the string they're displayed as, and deserialized by parsing it,
+//! which keeps identifiers readable, e.g. `"8086"` rather than `32902`, and does the same for the keys
+//! of the lookup tables of the pci.ids database.
+
+macro_rules! impl_serde_as_string {
+    ($($ty:ty),+ $(,)?) => {
+        $(
+            impl serde::Serialize for $ty {
+                fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.collect_str(self)
+                }
+            }
+
+            impl<'de> serde::Deserialize<'de> for $ty {
+                fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    let s = String::deserialize(deserializer)?;
+                    s.parse().map_err(serde::de::Error::custom)
+                }
+            }
+        )+
+    };
+}
+
+pub(crate) use impl_serde_as_string;
+
+/// This module serializes maps keyed by IDs with the keys written the way pci.ids writes them,
+/// e.g. `"8086"` or `"17aa:2292"`, to be used through `#[serde(with = "crate::serde_string::hex_keys")]`.
+pub(crate) mod hex_keys {
+    use serde::de::Error as _;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::HashMap;
+    use std::hash::Hash;
+
+    pub(crate) trait HexKey: Sized {
+        fn to_hex(&self) -> String;
+        fn from_hex(s: &str) -> Option<Self>;
+    }
+
+    impl HexKey for u8 {
+        fn to_hex(&self) -> String {
+            format!("{:02x}", self)
+        }
+
+        fn from_hex(s: &str) -> Option<Self> {
+            Some(s).filter(|s| s.len() == 2 && s.bytes().all(|b| b.is_ascii_hexdigit()))?;
+            u8::from_str_radix(s, 16).ok()
+        }
+    }
+
+    impl HexKey for u16 {
+        fn to_hex(&self) -> String {
+            format!("{:04x}", self)
+        }
+
+        fn from_hex(s: &str) -> Option<Self> {
+            Some(s).filter(|s| s.len() == 4 && s.bytes().all(|b| b.is_ascii_hexdigit()))?;
+            u16::from_str_radix(s, 16).ok()
+        }
+    }
+
+    impl HexKey for (u16, u16) {
+        fn to_hex(&self) -> String {
+            format!("{}:{}", self.0.to_hex(), self.1.to_hex())
+        }
+
+        fn from_hex(s: &str) -> Option<Self> {
+            let (vendor, device) = s.split_once(':')?;
+            Some((u16::from_hex(vendor)?, u16::from_hex(device)?))
+        }
+    }
+
+    pub(crate) fn serialize<K, V, S>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        K: HexKey,
+        V: Serialize,
+        S: Serializer,
+    {
+        serializer.collect_map(map.iter().map(|(key, value)| (key.to_hex(), value)))
+    }
+
+    pub(crate) fn deserialize<'de, K, V, D>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+    where
+        K: HexKey + Eq + Hash,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        HashMap::<String, V>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(key, value)| match K::from_hex(&key) {
+                Some(id) => Ok((id, value)),
+                None => Err(D::Error::custom(format!("invalid identifier: {:?}", key))),
+            })
+            .collect()
+    }
+}
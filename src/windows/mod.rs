@@ -1,6 +1,10 @@
 #![doc(hidden)]
+use crate::address::PciAddress;
+use crate::context::Context;
 use crate::device_class::*;
-use crate::extra::*;
+use crate::error::Result;
+use crate::ids::{ClassCode, DeviceId, Revision, SubsystemId, VendorId};
+use crate::pci_ids::PciIds;
 use crate::private::Properties;
 use crate::Device;
 use std::path::PathBuf;
@@ -8,25 +12,29 @@ use std::path::PathBuf;
 #[derive(Debug)]
 pub struct WindowsPCIDevice {
     path: PathBuf,
-    address: String,
-    class_id: Vec<u8>,
+    address: PciAddress,
+    class_id: ClassCode,
     class_name: String,
     subclass_name: String,
-    vendor_id: Vec<u8>,
+    prog_if_name: String,
+    vendor_id: VendorId,
     vendor_name: String,
-    device_id: Vec<u8>,
+    device_id: DeviceId,
     device_name: String,
-    revision: Vec<u8>,
+    revision: Revision,
     numa_node: isize,
     enabled: bool,
     d3cold_allowed: bool,
-    subsystem_vendor_id: Vec<u8>,
-    subsystem_device_id: Vec<u8>,
+    subsystem_id: SubsystemId,
     subsystem_name: String,
 }
 
 impl Device for WindowsPCIDevice {
-    fn new(path: &str) -> Self {
+    fn with_context(path: &str, context: &Context) -> Self {
+        todo!()
+    }
+
+    fn try_with_context(path: &str, context: &Context) -> Result<Self> {
         todo!()
     }
 
@@ -34,19 +42,19 @@ impl Device for WindowsPCIDevice {
         todo!()
     }
 
-    fn address(&self) -> String {
+    fn pci_address(&self) -> PciAddress {
         todo!()
     }
 
-    fn class_id(&self) -> Vec<u8> {
+    fn class_code(&self) -> ClassCode {
         todo!()
     }
 
-    fn vendor_id(&self) -> Vec<u8> {
+    fn vendor(&self) -> VendorId {
         todo!()
     }
 
-    fn device_id(&self) -> Vec<u8> {
+    fn device(&self) -> DeviceId {
         todo!()
     }
 
@@ -62,6 +70,10 @@ impl Device for WindowsPCIDevice {
         todo!()
     }
 
+    fn prog_if_name(&self) -> String {
+        todo!()
+    }
+
     fn vendor_name(&self) -> String {
         todo!()
     }
@@ -78,7 +90,7 @@ impl Device for WindowsPCIDevice {
         todo!()
     }
 
-    fn revision(&self) -> Vec<u8> {
+    fn revision_id(&self) -> Revision {
         todo!()
     }
 
@@ -86,17 +98,13 @@ impl Device for WindowsPCIDevice {
         todo!()
     }
 
-    fn subsystem_vendor_id(&self) -> Vec<u8> {
-        todo!()
-    }
-
-    fn subsystem_device_id(&self) -> Vec<u8> {
+    fn subsystem(&self) -> SubsystemId {
         todo!()
     }
 }
 
 impl Properties for WindowsPCIDevice {
-    fn reserved_new(path: &str) -> Self {
+    fn reserved_new(path: &str, context: &Context) -> Self {
         todo!()
     }
 
@@ -104,35 +112,35 @@ impl Properties for WindowsPCIDevice {
         todo!()
     }
 
-    fn set_address(&mut self) {
+    fn set_address(&mut self) -> Result<()> {
         todo!()
     }
 
-    fn set_class_id(&mut self) {
+    fn set_class_id(&mut self) -> Result<()> {
         todo!()
     }
 
-    fn set_vendor_id(&mut self) {
+    fn set_vendor_id(&mut self) -> Result<()> {
         todo!()
     }
 
-    fn set_device_id(&mut self) {
+    fn set_device_id(&mut self) -> Result<()> {
         todo!()
     }
 
-    fn set_revision(&mut self) {
+    fn set_revision(&mut self) -> Result<()> {
         todo!()
     }
 
-    fn set_numa_node(&mut self) {
+    fn set_numa_node(&mut self) -> Result<()> {
         todo!()
     }
 
-    fn set_subsystem_vendor_id(&mut self) {
+    fn set_subsystem_vendor_id(&mut self) -> Result<()> {
         todo!()
     }
 
-    fn set_subsystem_device_id(&mut self) {
+    fn set_subsystem_device_id(&mut self) -> Result<()> {
         todo!()
     }
 
@@ -140,27 +148,31 @@ impl Properties for WindowsPCIDevice {
         todo!()
     }
 
-    fn set_subclass_name(&mut self) {
+    fn set_subclass_name(&mut self, ids: &PciIds) {
+        todo!()
+    }
+
+    fn set_prog_if_name(&mut self, ids: &PciIds) {
         todo!()
     }
 
-    fn set_vendor_name(&mut self) {
+    fn set_vendor_name(&mut self, ids: &PciIds) {
         todo!()
     }
 
-    fn set_device_name(&mut self) {
+    fn set_device_name(&mut self, ids: &PciIds) {
         todo!()
     }
 
-    fn set_subsystem_name(&mut self) {
+    fn set_subsystem_name(&mut self, ids: &PciIds) {
         todo!()
     }
 
-    fn set_enabled(&mut self) {
+    fn set_enabled(&mut self) -> Result<()> {
         todo!()
     }
 
-    fn set_d3cold_allowed(&mut self) {
+    fn set_d3cold_allowed(&mut self) -> Result<()> {
         todo!()
     }
 }
@@ -169,18 +181,18 @@ impl Default for WindowsPCIDevice {
     fn default() -> Self {
         WindowsPCIDevice {
             path: PathBuf::new(),
-            address: String::new(),
+            address: PciAddress::default(),
             class_name: String::new(),
             subclass_name: String::new(),
+            prog_if_name: String::new(),
             vendor_name: String::new(),
             device_name: String::new(),
             subsystem_name: String::new(),
-            class_id: vec![],
-            subsystem_vendor_id: vec![],
-            subsystem_device_id: vec![],
-            device_id: vec![],
-            revision: vec![],
-            vendor_id: vec![],
+            class_id: ClassCode::default(),
+            subsystem_id: SubsystemId::default(),
+            device_id: DeviceId::default(),
+            revision: Revision::default(),
+            vendor_id: VendorId::default(),
             numa_node: -1,
             d3cold_allowed: false,
             enabled: false,